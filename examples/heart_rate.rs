@@ -11,7 +11,7 @@ fn main() {
 
 fn average_hr(path_buf: Option<PathBuf>) -> Result<f32> {
     let rates: Vec<i32> = parser::get_packets(
-        &path_buf
+        path_buf
             .as_ref()
             .unwrap()
             .clone()
@@ -0,0 +1,381 @@
+//! Analysis functions
+use crate::metadata::MetadataTable;
+use crate::types::{DataPacket, Sample, TypeTag};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// EmotiBit packet numbers are 16-bit counters that wrap around
+const PACKET_ID_MODULUS: i64 = 1 << 16;
+/// A backward jump of `emotibit_timestamp` larger than this (in milliseconds) is treated as a reboot
+const RESET_TOLERANCE_MS: f64 = 1000.0;
+
+/// Returns `true` if `next` appears to come from a restarted EmotiBit
+pub(crate) fn is_reset(prev: &DataPacket, next: &DataPacket) -> bool {
+    next.emotibit_timestamp < prev.emotibit_timestamp - RESET_TOLERANCE_MS
+}
+
+/// A point where the EmotiBit counters started over
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterReset {
+    /// Index of the first packet after the reset
+    pub index: usize,
+    /// Last `packet_id` before the reset
+    pub packet_id_before: u32,
+    /// First `packet_id` after the reset
+    pub packet_id_after: u32,
+    /// Last `emotibit_timestamp` before the reset
+    pub emotibit_timestamp_before: f64,
+    /// First `emotibit_timestamp` after the reset
+    pub emotibit_timestamp_after: f64,
+}
+
+/// Sequence statistics of a single TypeTag
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagSequenceStats {
+    /// Packets received, including duplicates
+    pub received: usize,
+    /// Packets whose `packet_id` had already been received
+    pub duplicates: usize,
+    /// Packets that arrived after a packet with a higher `packet_id`
+    pub out_of_order: usize,
+}
+
+/// Packet loss and sequence report built from `packet_id`s
+///
+/// `packet_id` is shared by all TypeTags, so missing packets cannot be attributed to a TypeTag.
+/// Sequence numbers are unwrapped, so IDs after a 16-bit wrap-around continue past 65535.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PacketLossReport {
+    /// Packets received, including duplicates
+    pub received: usize,
+    /// Packets expected from the range of `packet_id`s in each session
+    pub expected: u64,
+    /// Sequence numbers that were never received
+    pub missing: Vec<u64>,
+    /// Sequence numbers received more than once
+    pub duplicates: Vec<u64>,
+    /// Sequence numbers that arrived after a higher one
+    pub out_of_order: Vec<u64>,
+    /// Points where the EmotiBit rebooted
+    pub resets: Vec<CounterReset>,
+    /// Statistics per TypeTag
+    pub by_tag: BTreeMap<TypeTag, TagSequenceStats>,
+}
+
+impl PacketLossReport {
+    /// Ratio of missing packets to expected packets
+    pub fn loss_ratio(&self) -> f64 {
+        if self.expected == 0 {
+            0.0
+        } else {
+            self.missing.len() as f64 / self.expected as f64
+        }
+    }
+}
+
+/// Walks packets in the order they were recorded and reports missing, duplicate and out-of-order `packet_id`s and counter resets
+pub fn packet_loss<'a, I>(packets: I) -> PacketLossReport
+where
+    I: IntoIterator<Item = &'a DataPacket>,
+{
+    let mut report = PacketLossReport::default();
+    let mut session = Sequence::default();
+    let mut prev: Option<&DataPacket> = None;
+
+    for (index, packet) in packets.into_iter().enumerate() {
+        if let Some(prev) = prev.filter(|prev| is_reset(prev, packet)) {
+            session.finish(&mut report);
+            session = Sequence::default();
+            report.resets.push(CounterReset {
+                index,
+                packet_id_before: prev.packet_id,
+                packet_id_after: packet.packet_id,
+                emotibit_timestamp_before: prev.emotibit_timestamp,
+                emotibit_timestamp_after: packet.emotibit_timestamp,
+            });
+        }
+        prev = Some(packet);
+
+        let stats = report.by_tag.entry(packet.data_type.tag()).or_default();
+        stats.received += 1;
+        report.received += 1;
+
+        match session.push(packet.packet_id) {
+            Arrival::Duplicate(n) => {
+                stats.duplicates += 1;
+                report.duplicates.push(n);
+            }
+            Arrival::OutOfOrder(n) => {
+                stats.out_of_order += 1;
+                report.out_of_order.push(n);
+            }
+            Arrival::InOrder => (),
+        }
+    }
+    session.finish(&mut report);
+    report
+}
+
+enum Arrival {
+    InOrder,
+    OutOfOrder(u64),
+    Duplicate(u64),
+}
+
+/// `packet_id`s of one session, unwrapped into a monotonic sequence
+#[derive(Default)]
+struct Sequence {
+    /// Lowest sequence number received
+    first: Option<u64>,
+    last: u64,
+    seen: HashSet<u64>,
+    missing: BTreeSet<u64>,
+}
+
+impl Sequence {
+    fn push(&mut self, packet_id: u32) -> Arrival {
+        let n = match self.first {
+            Some(_) => unwrap(packet_id, self.last),
+            None => {
+                self.first = Some(packet_id as u64);
+                self.last = packet_id as u64;
+                packet_id as u64
+            }
+        };
+        if !self.seen.insert(n) {
+            Arrival::Duplicate(n)
+        } else if n > self.last {
+            self.missing.extend(self.last + 1..n);
+            self.last = n;
+            Arrival::InOrder
+        } else if n < self.last {
+            self.missing.remove(&n);
+            if let Some(first) = self.first.filter(|&first| n < first) {
+                self.missing.extend(n + 1..first);
+                self.first = Some(n);
+            }
+            Arrival::OutOfOrder(n)
+        } else {
+            Arrival::InOrder
+        }
+    }
+
+    fn finish(self, report: &mut PacketLossReport) {
+        if let Some(first) = self.first {
+            report.expected += self.last - first + 1;
+            report.missing.extend(self.missing);
+        }
+    }
+}
+
+/// Picks the sequence number for `packet_id` closest to `last`, accounting for wrap-around
+fn unwrap(packet_id: u32, last: u64) -> u64 {
+    let laps =
+        (last as i64 - packet_id as i64 + PACKET_ID_MODULUS / 2).div_euclid(PACKET_ID_MODULUS);
+    (packet_id as i64 + laps.max(0) * PACKET_ID_MODULUS) as u64
+}
+
+/// Infers the sampling rate in Hz of each TypeTag with numeric samples
+///
+/// This is the `rate` of [`estimate_sampling_rates`], so both agree.
+pub fn infer_sampling_rates<'a, I>(packets: I) -> BTreeMap<TypeTag, f64>
+where
+    I: IntoIterator<Item = &'a DataPacket>,
+{
+    estimate_sampling_rates(packets, &MetadataTable::new(), 0.0)
+        .into_iter()
+        .map(|(tag, estimate)| (tag, estimate.rate))
+        .collect()
+}
+
+/// Sampling rate of a TypeTag estimated from packet timestamps
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateEstimate {
+    /// Estimated sampling rate in Hz
+    pub rate: f64,
+    /// 95% confidence interval of the sampling rate in Hz
+    pub confidence_interval: (f64, f64),
+    /// Mean period between samples in milliseconds
+    pub mean_period: f64,
+    /// Standard deviation of the period between samples in milliseconds
+    pub jitter: f64,
+    /// Number of packet intervals the estimate is based on
+    pub intervals: usize,
+    /// Number of intervals left out as gaps, e.g. from lost packets
+    pub gaps: usize,
+    /// Nominal sampling rate in Hz, if any
+    pub nominal: Option<f64>,
+    /// `true` if the estimate deviates from the nominal rate by more than the tolerance
+    pub deviates: bool,
+}
+
+/// Estimates the sampling rate of each TypeTag with numeric samples and compares it with the nominal rate in `metadata`
+///
+/// Intervals longer than twice the median are treated as gaps and left out. `tolerance` is relative, e.g. `0.05` for 5%.
+pub fn estimate_sampling_rates<'a, I>(
+    packets: I,
+    metadata: &MetadataTable,
+    tolerance: f64,
+) -> BTreeMap<TypeTag, RateEstimate>
+where
+    I: IntoIterator<Item = &'a DataPacket>,
+{
+    sample_periods(packets)
+        .into_iter()
+        .filter_map(|(tag, mut periods)| {
+            periods.sort_by(f64::total_cmp);
+            let median = *periods.get(periods.len() / 2)?;
+            let total = periods.len();
+            periods.retain(|&p| p <= 2.0 * median);
+
+            let n = periods.len() as f64;
+            let mean_period = periods.iter().sum::<f64>() / n;
+            let variance = if periods.len() > 1 {
+                periods
+                    .iter()
+                    .map(|p| (p - mean_period).powi(2))
+                    .sum::<f64>()
+                    / (n - 1.0)
+            } else {
+                0.0
+            };
+            let jitter = variance.sqrt();
+            let margin = 1.96 * jitter / n.sqrt();
+
+            let rate = 1000.0 / mean_period;
+            let nominal = metadata.get(tag).sampling_rate;
+            Some((
+                tag,
+                RateEstimate {
+                    rate,
+                    confidence_interval: (
+                        1000.0 / (mean_period + margin),
+                        1000.0 / (mean_period - margin).max(f64::MIN_POSITIVE),
+                    ),
+                    mean_period,
+                    jitter,
+                    intervals: periods.len(),
+                    gaps: total - periods.len(),
+                    nominal,
+                    deviates: nominal.is_some_and(|r| (rate - r).abs() > r * tolerance),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Returns the per-sample periods in milliseconds between consecutive packets of each TypeTag
+pub(crate) fn sample_periods<'a, I>(packets: I) -> HashMap<TypeTag, Vec<f64>>
+where
+    I: IntoIterator<Item = &'a DataPacket>,
+{
+    let mut last: HashMap<TypeTag, &DataPacket> = HashMap::new();
+    let mut periods: HashMap<TypeTag, Vec<f64>> = HashMap::new();
+    for packet in packets {
+        if packet.data_type.values().is_none() {
+            continue;
+        }
+        let tag = packet.data_type.tag();
+        if let Some(prev) = last.insert(tag, packet) {
+            let interval = packet.emotibit_timestamp - prev.emotibit_timestamp;
+            if interval > 0.0 && packet.data_points > 0 && !is_reset(prev, packet) {
+                periods
+                    .entry(tag)
+                    .or_default()
+                    .push(interval / packet.data_points as f64);
+            }
+        }
+    }
+    periods
+}
+
+/// Expands packets into samples per TypeTag
+///
+/// Sampling rates are taken from `rates` where known and inferred from the packets otherwise.
+pub fn expand_samples(
+    packets: &[DataPacket],
+    rates: &BTreeMap<TypeTag, f64>,
+) -> BTreeMap<TypeTag, Vec<Sample>> {
+    let inferred = infer_sampling_rates(packets);
+    let mut samples: BTreeMap<TypeTag, Vec<Sample>> = BTreeMap::new();
+    for packet in packets {
+        let tag = packet.data_type.tag();
+        let rate = rates.get(&tag).or_else(|| inferred.get(&tag));
+        samples
+            .entry(tag)
+            .or_default()
+            .extend(packet.samples(rate.copied().unwrap_or_default()));
+    }
+    samples.retain(|_, v| !v.is_empty());
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(ids: &[(f64, u32)]) -> Vec<DataPacket> {
+        ids.iter()
+            .map(|(timestamp, id)| {
+                DataPacket::try_from(format!("{},{},1,EA,1,100,0.1", timestamp, id).as_str())
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn out_of_order_below_first_id() {
+        let report = packet_loss(&packets(&[(1000.0, 10), (1001.0, 5), (1002.0, 11)]));
+        assert_eq!(report.expected, 7);
+        assert_eq!(report.missing, vec![6, 7, 8, 9]);
+        assert_eq!(report.out_of_order, vec![5]);
+    }
+
+    #[test]
+    fn unwrap_across_16_bit_wrap() {
+        assert_eq!(unwrap(2, 65535), 65538);
+        assert_eq!(unwrap(65535, 65538), 65535);
+        assert_eq!(unwrap(7, 5), 7);
+
+        let report = packet_loss(&packets(&[
+            (1000.0, 65534),
+            (1001.0, 65535),
+            (1002.0, 0),
+            (1003.0, 2),
+        ]));
+        assert_eq!(report.expected, 5);
+        assert_eq!(report.missing, vec![65537]);
+        assert!(report.out_of_order.is_empty());
+    }
+
+    #[test]
+    fn duplicates() {
+        let report = packet_loss(&packets(&[
+            (1000.0, 1),
+            (1001.0, 2),
+            (1001.0, 2),
+            (1002.0, 3),
+        ]));
+        assert_eq!(report.received, 4);
+        assert_eq!(report.expected, 3);
+        assert_eq!(report.duplicates, vec![2]);
+        assert!(report.missing.is_empty());
+        assert_eq!(report.by_tag[&TypeTag::EA].duplicates, 1);
+    }
+
+    #[test]
+    fn resets_start_a_new_sequence() {
+        let report = packet_loss(&packets(&[
+            (5000.0, 100),
+            (5001.0, 101),
+            (10.0, 1),
+            (11.0, 3),
+        ]));
+        assert_eq!(report.resets.len(), 1);
+        assert_eq!(report.resets[0].index, 2);
+        assert_eq!(report.resets[0].packet_id_before, 101);
+        assert_eq!(report.resets[0].packet_id_after, 1);
+        assert_eq!(report.expected, 5);
+        assert_eq!(report.missing, vec![2]);
+        assert!(report.out_of_order.is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+//! Apache Arrow conversion
+//!
+//! Packets are converted into one `RecordBatch` per TypeTag with one row per sample:
+//!
+//! | Column               | Type              | Description                             |
+//! |----------------------|-------------------|-----------------------------------------|
+//! | `host_timestamp`     | Float64, null     | Local timestamp on a host PC in seconds |
+//! | `emotibit_timestamp` | Float64           | Milliseconds since start of EmotiBit    |
+//! | `packet_id`          | UInt32            | Packet count since start of EmotiBit    |
+//! | `value`              | Float64 or Utf8   | Value of the sample                     |
+//!
+//! `value` is Float64 for TypeTags with numeric samples and Utf8 for the others, such as `TL`, `RB` or `UN`,
+//! whose rows carry the packet timestamps. The schema metadata holds the TypeTag under `type_tag`,
+//! and the `value` field holds the name and, if any, the unit of the TypeTag under `name` and `unit`.
+//!
+//! [`sample_schema`] is the long format of numeric samples of all TypeTags in a single table,
+//! with a Utf8 `type_tag` column before `value`.
+use crate::recording::Recording;
+use crate::types::{DataPacket, TypeTag};
+use arrow_array::{
+    builder::{Float64Builder, StringBuilder, UInt32Builder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// Returns the schema of numeric samples of all TypeTags in long format
+pub fn sample_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("host_timestamp", DataType::Float64, true),
+        Field::new("emotibit_timestamp", DataType::Float64, false),
+        Field::new("packet_id", DataType::UInt32, false),
+        Field::new("type_tag", DataType::Utf8, false),
+        Field::new("value", DataType::Float64, false),
+    ]))
+}
+
+/// Returns the schema of the `RecordBatch` of `tag`, with a Float64 `value` column if `numeric`
+pub fn tag_schema(tag: TypeTag, numeric: bool) -> SchemaRef {
+    let metadata = tag.metadata();
+    let mut value_metadata = HashMap::from([("name".to_owned(), metadata.name.into_owned())]);
+    if let Some(unit) = metadata.unit {
+        value_metadata.insert("unit".to_owned(), unit.into_owned());
+    }
+    let value_type = if numeric {
+        DataType::Float64
+    } else {
+        DataType::Utf8
+    };
+    let fields = vec![
+        Field::new("host_timestamp", DataType::Float64, true),
+        Field::new("emotibit_timestamp", DataType::Float64, false),
+        Field::new("packet_id", DataType::UInt32, false),
+        Field::new("value", value_type, false).with_metadata(value_metadata),
+    ];
+    Arc::new(Schema::new_with_metadata(
+        fields,
+        HashMap::from([("type_tag".to_owned(), tag.as_str().to_owned())]),
+    ))
+}
+
+/// Converts packets into a `RecordBatch` per TypeTag
+///
+/// Samples are spread back from the packet timestamp at the rate of their TypeTag in `sampling_rates`,
+/// or all get the packet timestamp if there is none. Packets of unknown TypeTags are left out.
+pub fn to_record_batches<'a, I>(
+    packets: I,
+    sampling_rates: &BTreeMap<TypeTag, f64>,
+) -> Result<BTreeMap<TypeTag, RecordBatch>, ArrowError>
+where
+    I: IntoIterator<Item = &'a DataPacket>,
+{
+    let mut columns: BTreeMap<TypeTag, SampleColumns> = BTreeMap::new();
+    for packet in packets {
+        let tag = packet.data_type.tag();
+        if tag == TypeTag::Unknown {
+            continue;
+        }
+        let sampling_rate = sampling_rates.get(&tag).copied().unwrap_or_default();
+        columns
+            .entry(tag)
+            .or_insert_with(|| {
+                SampleColumns::new(tag_schema(tag, packet.data_type.values().is_some()))
+            })
+            .append(packet, sampling_rate);
+    }
+    columns
+        .into_iter()
+        .map(|(tag, mut columns)| Ok((tag, columns.finish()?)))
+        .collect()
+}
+
+impl Recording {
+    /// Converts the packets into a `RecordBatch` per TypeTag, timestamping samples at `Recording::sampling_rates`
+    ///
+    /// See [`crate::arrow`] for the schema.
+    pub fn to_record_batches(&self) -> Result<BTreeMap<TypeTag, RecordBatch>, ArrowError> {
+        to_record_batches(self.packets(), &self.sampling_rates())
+    }
+}
+
+enum ValueColumn {
+    Numeric(Float64Builder),
+    Text(StringBuilder),
+}
+
+/// Column builders for one of the schemas above
+pub(crate) struct SampleColumns {
+    schema: SchemaRef,
+    host_timestamp: Float64Builder,
+    emotibit_timestamp: Float64Builder,
+    packet_id: UInt32Builder,
+    type_tag: Option<StringBuilder>,
+    value: ValueColumn,
+}
+
+impl SampleColumns {
+    /// Creates builders for `schema`, which must be `sample_schema` or a `tag_schema`
+    pub(crate) fn new(schema: SchemaRef) -> Self {
+        let type_tag = schema
+            .column_with_name("type_tag")
+            .map(|_| StringBuilder::new());
+        let value = match schema.field_with_name("value").map(Field::data_type) {
+            Ok(DataType::Float64) => ValueColumn::Numeric(Float64Builder::new()),
+            _ => ValueColumn::Text(StringBuilder::new()),
+        };
+        SampleColumns {
+            schema,
+            host_timestamp: Float64Builder::new(),
+            emotibit_timestamp: Float64Builder::new(),
+            packet_id: UInt32Builder::new(),
+            type_tag,
+            value,
+        }
+    }
+
+    /// Number of rows appended since the last `finish`
+    #[cfg(feature = "parquet")]
+    pub(crate) fn len(&self) -> usize {
+        arrow_array::builder::ArrayBuilder::len(&self.packet_id)
+    }
+
+    /// Appends one row per sample of `packet`
+    pub(crate) fn append(&mut self, packet: &DataPacket, sampling_rate: f64) {
+        let rows = match &mut self.value {
+            ValueColumn::Numeric(builder) => {
+                let samples = packet.samples(sampling_rate);
+                for sample in &samples {
+                    self.host_timestamp.append_option(sample.host_timestamp);
+                    self.emotibit_timestamp
+                        .append_value(sample.emotibit_timestamp);
+                    builder.append_value(sample.value);
+                }
+                samples.len()
+            }
+            ValueColumn::Text(builder) => {
+                let values =
+                    DataPacket::parse_data_type(&packet.data_type, packet.data_type.payload());
+                for value in &values {
+                    self.host_timestamp.append_option(packet.host_timestamp);
+                    self.emotibit_timestamp
+                        .append_value(packet.emotibit_timestamp);
+                    builder.append_value(value);
+                }
+                values.len()
+            }
+        };
+        for _ in 0..rows {
+            self.packet_id.append_value(packet.packet_id);
+            if let Some(type_tag) = &mut self.type_tag {
+                type_tag.append_value(packet.data_type.as_str());
+            }
+        }
+    }
+
+    /// Builds a `RecordBatch` from the rows appended so far and resets the builders
+    pub(crate) fn finish(&mut self) -> Result<RecordBatch, ArrowError> {
+        let mut arrays: Vec<ArrayRef> = vec![
+            Arc::new(self.host_timestamp.finish()),
+            Arc::new(self.emotibit_timestamp.finish()),
+            Arc::new(self.packet_id.finish()),
+        ];
+        if let Some(type_tag) = &mut self.type_tag {
+            arrays.push(Arc::new(type_tag.finish()));
+        }
+        arrays.push(match &mut self.value {
+            ValueColumn::Numeric(builder) => Arc::new(builder.finish()),
+            ValueColumn::Text(builder) => Arc::new(builder.finish()),
+        });
+        RecordBatch::try_new(self.schema.clone(), arrays)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Mode, PacketReader};
+    use arrow_array::{cast::AsArray, types::Float64Type};
+
+    #[test]
+    fn one_batch_per_tag() {
+        let packets: Vec<DataPacket> = PacketReader::new(
+            "1000,1,2,EA,1,100,0.1,0.2\n\
+             1010,2,2,UN,1,100,a,note\n\
+             1020,3,1,XX,1,100,1\n\
+             1030,4,1,EA,1,100,0.3\n"
+                .as_bytes(),
+        )
+        .mode(Mode::Lenient)
+        .map(Result::unwrap)
+        .collect();
+        let rates = BTreeMap::from([(TypeTag::EA, 10.0)]);
+        let batches = to_record_batches(&packets, &rates).unwrap();
+
+        // Unknown TypeTags are left out
+        assert_eq!(
+            batches.keys().copied().collect::<Vec<_>>(),
+            [TypeTag::EA, TypeTag::UN]
+        );
+
+        let ea = &batches[&TypeTag::EA];
+        assert_eq!(ea.schema(), tag_schema(TypeTag::EA, true));
+        assert_eq!(ea.schema().metadata()["type_tag"], "EA");
+        let value = ea.schema().field_with_name("value").unwrap().clone();
+        assert_eq!(value.data_type(), &DataType::Float64);
+        assert_eq!(value.metadata()["name"], "EDA");
+        assert_eq!(value.metadata()["unit"], "uS");
+        assert_eq!(ea.num_rows(), 3);
+        assert_eq!(ea.column(0).null_count(), 3);
+        assert_eq!(
+            ea.column(1).as_primitive::<Float64Type>().values(),
+            &[900.0, 1000.0, 1030.0]
+        );
+        assert_eq!(
+            ea.column(3).as_primitive::<Float64Type>().values(),
+            &[0.1f32 as f64, 0.2f32 as f64, 0.3f32 as f64]
+        );
+
+        // Text values are Utf8 rows at the packet timestamp
+        let un = &batches[&TypeTag::UN];
+        assert_eq!(un.schema().metadata()["type_tag"], "UN");
+        let value = un.schema().field_with_name("value").unwrap().clone();
+        assert_eq!(value.data_type(), &DataType::Utf8);
+        assert_eq!(value.metadata()["name"], "User Note");
+        assert!(!value.metadata().contains_key("unit"));
+        assert_eq!(
+            un.column(3).as_string::<i32>().iter().collect::<Vec<_>>(),
+            [Some("a"), Some("note")]
+        );
+        assert_eq!(
+            un.column(1).as_primitive::<Float64Type>().values(),
+            &[1010.0, 1010.0]
+        );
+    }
+}
@@ -0,0 +1,474 @@
+//! European Data Format (EDF+ and BDF+) export
+//!
+//! Every TypeTag with numeric samples becomes a signal. Samples are linearly interpolated onto data records
+//! of a fixed duration at the sampling rate of the TypeTag, rounded to a whole number of samples per record,
+//! and the first and last values are held before and after the samples.
+//! Signals are labelled with their TypeTag, and the transducer field names the signal.
+//! Physical units and ranges come from the metadata of the recording. TypeTags without a physical range,
+//! such as heart rate, get the range of their resampled values.
+//! `UN` and `LM` packets are written as EDF+ annotations.
+//!
+//! Time runs in EmotiBit time from the first packet. The start date and time are taken from the host timestamps,
+//! if any. EmotiBit time starts over on a reboot, so each session of a recording is written to its own file.
+use crate::error::ParseError;
+use crate::recording::{Recording, RecordingSession};
+use crate::types::{DataPacket, TypeTag};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local, TimeZone};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Size in bytes of the fixed header and of the header of each signal
+const HEADER_SIZE: usize = 256;
+/// Widths of the fields of a signal header, from label to reserved
+const SIGNAL_FIELD_WIDTHS: [usize; 10] = [16, 80, 8, 8, 8, 8, 8, 80, 8, 32];
+
+/// Variant of the European Data Format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EdfFormat {
+    /// EDF+ with 16-bit samples
+    #[default]
+    Edf,
+    /// BDF+ with 24-bit samples
+    Bdf,
+}
+
+impl EdfFormat {
+    fn version(&self) -> &'static [u8] {
+        match self {
+            EdfFormat::Edf => b"0",
+            EdfFormat::Bdf => b"\xFFBIOSEMI",
+        }
+    }
+
+    fn reserved(&self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "EDF+C",
+            EdfFormat::Bdf => "BDF+C",
+        }
+    }
+
+    fn annotations_label(&self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "EDF Annotations",
+            EdfFormat::Bdf => "BDF Annotations",
+        }
+    }
+
+    fn digital_range(&self) -> (i32, i32) {
+        match self {
+            EdfFormat::Edf => (i16::MIN as i32, i16::MAX as i32),
+            EdfFormat::Bdf => (-(1 << 23), (1 << 23) - 1),
+        }
+    }
+
+    fn bytes_per_sample(&self) -> usize {
+        match self {
+            EdfFormat::Edf => 2,
+            EdfFormat::Bdf => 3,
+        }
+    }
+}
+
+/// Use `EdfWriterBuilder` to build this struct.
+pub struct EdfWriter<W: Write = File> {
+    writer: W,
+    format: EdfFormat,
+    record_duration: f64,
+    patient_code: String,
+}
+
+impl<W: Write> EdfWriter<W> {
+    /// Writes a recording as a complete file and returns the underlying writer
+    ///
+    /// Fails if the EmotiBit rebooted during the recording. Use `write_session` to write each session instead.
+    pub fn write(self, recording: &Recording) -> Result<W> {
+        let mut sessions = recording.sessions();
+        match (sessions.next(), sessions.len()) {
+            (Some(session), 0) => self.write_session(recording, session),
+            (None, _) => Err(ParseError::NoPackets.into()),
+            (Some(_), more) => bail!(
+                "the recording has {} sessions between EmotiBit reboots, write them one at a time",
+                more + 1
+            ),
+        }
+    }
+
+    /// Writes a session of `recording` as a complete file and returns the underlying writer
+    pub fn write_session(self, recording: &Recording, session: RecordingSession) -> Result<W> {
+        let packets = session.packets();
+        let start = packets
+            .iter()
+            .map(|p| p.emotibit_timestamp)
+            .reduce(f64::min)
+            .ok_or(ParseError::NoPackets)?;
+        let end = packets
+            .iter()
+            .map(|p| p.emotibit_timestamp)
+            .fold(start, f64::max);
+        let seconds = |p: &DataPacket| (p.emotibit_timestamp - start) / 1000.0;
+        let (duration_text, duration) = fit(self.record_duration, |x| x);
+        let records = ((end - start) / 1000.0 / duration).floor() as usize + 1;
+
+        // Start date and time, with the fraction of a second as the onset of the first data record
+        let host_start = packets
+            .iter()
+            .find_map(|p| Some(p.host_timestamp? - seconds(p)));
+        let (start_time, offset) = match host_start {
+            Some(t) => (
+                Local.timestamp_opt(t.floor() as i64, 0).single(),
+                t - t.floor(),
+            ),
+            None => (None, 0.0),
+        };
+
+        let sampling_rates = recording.sampling_rates();
+        let tags: BTreeSet<TypeTag> = packets.iter().map(|p| p.data_type.tag()).collect();
+        let mut signals = vec![];
+        for tag in tags {
+            let Some(&rate) = sampling_rates.get(&tag) else {
+                continue;
+            };
+            let mut series: Vec<(f64, f64)> = packets
+                .iter()
+                .filter(|p| p.data_type.tag() == tag)
+                .flat_map(|p| p.samples(rate))
+                .filter(|s| s.value.is_finite())
+                .map(|s| ((s.emotibit_timestamp - start) / 1000.0, s.value))
+                .collect();
+            if tag == TypeTag::Unknown || series.is_empty() {
+                continue;
+            }
+            series.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let samples_per_record = ((rate * duration).round() as usize).max(1);
+            let values = resample(
+                &series,
+                duration / samples_per_record as f64,
+                records * samples_per_record,
+            );
+            let metadata = recording.metadata().get(tag);
+            // Units from the info file such as "microsiemens" do not fit the 8-character field
+            let unit = metadata
+                .unit
+                .filter(|unit| unit.len() <= 8)
+                .or(tag.metadata().unit);
+            signals.push(Signal::new(
+                tag.as_str(),
+                &format!("EmotiBit {}", metadata.name),
+                unit.as_deref().unwrap_or_default(),
+                metadata.physical_range,
+                samples_per_record,
+                &values,
+                self.format,
+            ));
+        }
+
+        // One time-keeping TAL per data record, followed by the notes that start in it
+        let mut tals: Vec<String> = (0..records)
+            .map(|i| format!("+{}\x14\x14\0", seconds_str(offset + i as f64 * duration)))
+            .collect();
+        for packet in packets {
+            if !matches!(packet.data_type.tag(), TypeTag::UN | TypeTag::LM) {
+                continue;
+            }
+            let onset = seconds(packet);
+            let text = DataPacket::parse_data_type(&packet.data_type, packet.data_type.payload())
+                .join(",")
+                .replace(['\x14', '\x15', '\0'], " ");
+            let record = ((onset / duration).floor() as usize).min(records - 1);
+            tals[record].push_str(&format!(
+                "+{}\x14{}\x14\0",
+                seconds_str(offset + onset),
+                text
+            ));
+        }
+        let bytes_per_sample = self.format.bytes_per_sample();
+        let annotation_samples = tals
+            .iter()
+            .map(|tal| tal.len().div_ceil(bytes_per_sample))
+            .max()
+            .unwrap_or(1);
+        let (digital_min, digital_max) = self.format.digital_range();
+        let annotations = Signal {
+            label: self.format.annotations_label().to_owned(),
+            transducer: String::new(),
+            unit: String::new(),
+            physical_min: "-1".to_owned(),
+            physical_max: "1".to_owned(),
+            digital_min,
+            digital_max,
+            samples_per_record: annotation_samples,
+            data: vec![],
+        };
+
+        let mut out = BufWriter::new(self.writer);
+
+        // Header
+        let equipment = match recording.info().and_then(|info| info.device_id.as_deref()) {
+            Some(id) => format!("EmotiBit_{}", subfield(id)),
+            None => "EmotiBit".to_owned(),
+        };
+        let startdate = start_time
+            .as_ref()
+            .map(|dt| dt.format("%d-%b-%Y").to_string().to_uppercase())
+            .unwrap_or_else(|| "X".to_owned());
+        let ns = signals.len() + 1;
+        out.write_all(field_bytes(self.format.version(), 8).as_slice())?;
+        write!(
+            out,
+            "{}{}{}{}{}{}{}{}{}",
+            field(&format!("{} X X X", subfield(&self.patient_code)), 80),
+            field(&format!("Startdate {} X X {}", startdate, equipment), 80),
+            field(&date_or_default(&start_time, "%d.%m.%y", "01.01.85"), 8),
+            field(&date_or_default(&start_time, "%H.%M.%S", "00.00.00"), 8),
+            field(&(HEADER_SIZE * (ns + 1)).to_string(), 8),
+            field(self.format.reserved(), 44),
+            field(&records.to_string(), 8),
+            field(trim_zeros(&duration_text), 8),
+            field(&ns.to_string(), 4),
+        )?;
+        // Signal headers are written field by field across all signals
+        let headers: Vec<[String; 10]> = signals
+            .iter()
+            .chain(std::iter::once(&annotations))
+            .map(Signal::header)
+            .collect();
+        for (i, width) in SIGNAL_FIELD_WIDTHS.into_iter().enumerate() {
+            for header in &headers {
+                out.write_all(field(&header[i], width).as_bytes())?;
+            }
+        }
+
+        // Data records
+        for (i, tal) in tals.iter().enumerate() {
+            for signal in &signals {
+                let n = signal.samples_per_record;
+                for &sample in &signal.data[i * n..(i + 1) * n] {
+                    out.write_all(&sample.to_le_bytes()[..bytes_per_sample])?;
+                }
+            }
+            let mut bytes = tal.as_bytes().to_vec();
+            bytes.resize(annotation_samples * bytes_per_sample, 0);
+            out.write_all(&bytes)?;
+        }
+
+        Ok(out.into_inner().map_err(|e| e.into_error())?)
+    }
+}
+
+/// Builder struct for `EdfWriter`
+pub struct EdfWriterBuilder {
+    format: EdfFormat,
+    record_duration: f64,
+    patient_code: String,
+}
+
+impl Default for EdfWriterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EdfWriterBuilder {
+    pub fn new() -> Self {
+        EdfWriterBuilder {
+            format: EdfFormat::default(),
+            record_duration: 1.0,
+            patient_code: "X".to_owned(),
+        }
+    }
+    /// Sets the format. Defaults to EDF+.
+    pub fn format(mut self, format: EdfFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Sets the duration of a data record in seconds. Defaults to 1.
+    pub fn record_duration(mut self, seconds: f64) -> Self {
+        if seconds > 0.0 {
+            self.record_duration = seconds;
+        }
+        self
+    }
+    /// Sets the patient code in the patient identification. Defaults to `X`, i.e. unknown.
+    pub fn patient_code(mut self, code: &str) -> Self {
+        self.patient_code = code.to_owned();
+        self
+    }
+    /// Creates `EdfWriter` with a file path
+    pub fn from_path<P: AsRef<Path>>(self, path: P) -> Result<EdfWriter> {
+        Ok(self.from_writer(File::create(path)?))
+    }
+    /// Creates `EdfWriter` with any `io::Write`
+    pub fn from_writer<W: Write>(self, writer: W) -> EdfWriter<W> {
+        EdfWriter {
+            writer,
+            format: self.format,
+            record_duration: self.record_duration,
+            patient_code: self.patient_code,
+        }
+    }
+}
+
+/// A signal with its header fields and digital samples of all data records
+struct Signal {
+    label: String,
+    transducer: String,
+    unit: String,
+    physical_min: String,
+    physical_max: String,
+    digital_min: i32,
+    digital_max: i32,
+    samples_per_record: usize,
+    data: Vec<i32>,
+}
+
+impl Signal {
+    fn new(
+        label: &str,
+        transducer: &str,
+        unit: &str,
+        physical_range: Option<(f64, f64)>,
+        samples_per_record: usize,
+        values: &[f64],
+        format: EdfFormat,
+    ) -> Self {
+        let (mut min, mut max) = physical_range.unwrap_or_else(|| {
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            (min, max)
+        });
+        if min >= max {
+            min -= 1.0;
+            max += 1.0;
+        }
+        // The physical range is rounded outwards to fit in the header
+        let (physical_min, min) = fit(min, f64::floor);
+        let (physical_max, max) = fit(max, f64::ceil);
+        let (digital_min, digital_max) = format.digital_range();
+        let scale = (digital_max - digital_min) as f64 / (max - min);
+        let data = values
+            .iter()
+            .map(|v| {
+                ((v - min) * scale + digital_min as f64)
+                    .round()
+                    .clamp(digital_min as f64, digital_max as f64) as i32
+            })
+            .collect();
+        Signal {
+            label: label.to_owned(),
+            transducer: transducer.to_owned(),
+            unit: unit.to_owned(),
+            physical_min,
+            physical_max,
+            digital_min,
+            digital_max,
+            samples_per_record,
+            data,
+        }
+    }
+
+    /// Returns the fields of the signal header
+    fn header(&self) -> [String; 10] {
+        [
+            self.label.clone(),
+            self.transducer.clone(),
+            self.unit.clone(),
+            self.physical_min.clone(),
+            self.physical_max.clone(),
+            self.digital_min.to_string(),
+            self.digital_max.to_string(),
+            String::new(),
+            self.samples_per_record.to_string(),
+            String::new(),
+        ]
+    }
+}
+
+/// Linearly interpolates `series`, sorted by time in seconds, at `count` points `period` seconds apart from 0
+fn resample(series: &[(f64, f64)], period: f64, count: usize) -> Vec<f64> {
+    let mut j = 0;
+    (0..count)
+        .map(|k| {
+            let t = k as f64 * period;
+            while j + 1 < series.len() && series[j + 1].0 <= t {
+                j += 1;
+            }
+            let (t0, v0) = series[j];
+            match series.get(j + 1) {
+                Some(&(t1, v1)) if t > t0 && t1 > t0 => v0 + (v1 - v0) * (t - t0) / (t1 - t0),
+                _ => v0,
+            }
+        })
+        .collect()
+}
+
+/// Formats `value` in at most 8 characters with as many decimals as fit, rounded with `round`
+///
+/// Returns the text and the value it represents.
+fn fit(value: f64, round: fn(f64) -> f64) -> (String, f64) {
+    for decimals in (0..=6).rev() {
+        let scale = 10_f64.powi(decimals);
+        let rounded = round(value * scale) / scale;
+        let text = format!("{:.*}", decimals as usize, rounded);
+        if text.len() <= 8 {
+            return (text, rounded);
+        }
+    }
+    let clamped = value.clamp(-9_999_999.0, 99_999_999.0);
+    (format!("{:.0}", clamped), clamped)
+}
+
+/// Formats seconds for a TAL
+fn seconds_str(seconds: f64) -> String {
+    trim_zeros(&format!("{:.6}", seconds)).to_owned()
+}
+
+fn trim_zeros(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+fn date_or_default(date_time: &Option<DateTime<Local>>, format: &str, default: &str) -> String {
+    date_time
+        .as_ref()
+        .map(|dt| dt.format(format).to_string())
+        .unwrap_or_else(|| default.to_owned())
+}
+
+/// Replaces spaces in a subfield of the patient or recording identification
+fn subfield(text: &str) -> String {
+    if text.is_empty() {
+        "X".to_owned()
+    } else {
+        text.replace(' ', "_")
+    }
+}
+
+/// Truncates or pads `text` with spaces to `width` printable ASCII characters
+fn field(text: &str, width: usize) -> String {
+    let text: String = text
+        .chars()
+        .map(|c| {
+            if c == ' ' || c.is_ascii_graphic() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(width)
+        .collect();
+    format!("{:<width$}", text)
+}
+
+fn field_bytes(bytes: &[u8], width: usize) -> Vec<u8> {
+    let mut bytes = bytes[..bytes.len().min(width)].to_vec();
+    bytes.resize(width, b' ');
+    bytes
+}
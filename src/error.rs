@@ -0,0 +1,247 @@
+//! Error types for this crate
+use csv::StringRecord;
+use itertools::Itertools;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// A `Result` alias where the error defaults to `ParseError`
+pub type Result<T, E = ParseError> = std::result::Result<T, E>;
+
+/// Position of a record in the raw data
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Position {
+    /// File the record was read from, if known
+    pub file: Option<Arc<Path>>,
+    /// Line number, starting at 1
+    pub line: u64,
+    /// Byte offset from the start of the input
+    pub byte: u64,
+}
+
+impl From<&csv::Position> for Position {
+    fn from(p: &csv::Position) -> Self {
+        Position {
+            file: None,
+            line: p.line(),
+            byte: p.byte(),
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file.display())?;
+        }
+        write!(f, "line {}, byte {}", self.line, self.byte)
+    }
+}
+
+/// A raw record and where it came from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    /// Position of the record, if it was read from a stream
+    pub position: Option<Position>,
+    /// The record as it appeared in the raw data
+    pub raw: String,
+}
+
+impl From<&StringRecord> for Record {
+    fn from(r: &StringRecord) -> Self {
+        Record {
+            position: r.position().map(Position::from),
+            raw: r.iter().join(","),
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.position {
+            Some(position) => write!(f, "{}, record: {:?}", position, self.raw),
+            None => write!(f, "record: {:?}", self.raw),
+        }
+    }
+}
+
+/// Errors returned by the parser
+#[derive(Debug, Error)]
+pub enum ParseError {
+    /// The record ended before the given column
+    #[error("Missing Column {column}, {record}")]
+    MissingColumn { column: usize, record: Record },
+    /// The TypeTag is not known to this crate
+    #[error("Unknown Type: {tag}, {record}")]
+    UnknownTypeTag { tag: String, record: Record },
+    /// A value could not be parsed as a number
+    #[error("Parse to Num Error: {value:?} in column {column}, {record}")]
+    InvalidNumber {
+        column: usize,
+        value: String,
+        record: Record,
+    },
+    /// The number of payload values differs from `data_points`
+    #[error("Data points mismatch: expected {expected}, found {actual}, {record}")]
+    DataPointsMismatch {
+        expected: u8,
+        actual: usize,
+        record: Record,
+    },
+    /// A payload that should be JSON, such as the one of `EI`, could not be parsed
+    #[error("Invalid JSON payload: {source}, {record}")]
+    InvalidJson {
+        source: serde_json::Error,
+        record: Record,
+    },
+    /// A TX packet does not carry a known pair of TypeTags and values
+    #[error("Invalid TX payload, {record}")]
+    InvalidTxPayload { record: Record },
+    /// The TS_sent field of a time sync is not in `%Y-%m-%d_%H-%M-%S-f` format
+    #[error("Invalid date string: {0:?}")]
+    InvalidTimestamp(String),
+    /// There are too few RD, TL and AK packets to find time syncs
+    #[error("Not enough sync data: found {found}, required {required}")]
+    NotEnoughSyncs { found: usize, required: usize },
+    /// The time syncs are not spread widely enough to generate a time sync map
+    #[error("Cannot generate a time sync map from {0} time syncs")]
+    NoTimeSyncMap(usize),
+    /// The `_info.json` sidecar is not in the expected format
+    #[error("Invalid info file: {0}")]
+    InvalidInfo(String),
+    /// The `_info.json` sidecar exists but could not be read
+    #[error("Cannot read info file {}: {source}", .path.display())]
+    InfoFile {
+        path: PathBuf,
+        source: Box<ParseError>,
+    },
+    /// The `_info.json` sidecar is not valid JSON
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// There are no packets to work with
+    #[error("No data packets")]
+    NoPackets,
+    /// The CSV reader failed
+    #[error("{source}")]
+    Csv {
+        source: csv::Error,
+        position: Option<Position>,
+    },
+    /// Reading the input failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// A problem found in a record that was still turned into a packet
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Warning {
+    /// The number of payload values differs from `data_points`
+    #[error("Data points mismatch: expected {expected}, found {actual}, {record}")]
+    DataPointsMismatch {
+        expected: u8,
+        actual: usize,
+        record: Record,
+    },
+}
+
+impl Warning {
+    /// Returns the record that caused this warning
+    pub fn record(&self) -> &Record {
+        match self {
+            Warning::DataPointsMismatch { record, .. } => record,
+        }
+    }
+}
+
+impl From<Warning> for ParseError {
+    fn from(warning: Warning) -> Self {
+        match warning {
+            Warning::DataPointsMismatch {
+                expected,
+                actual,
+                record,
+            } => ParseError::DataPointsMismatch {
+                expected,
+                actual,
+                record,
+            },
+        }
+    }
+}
+
+impl From<csv::Error> for ParseError {
+    fn from(source: csv::Error) -> Self {
+        ParseError::Csv {
+            position: source.position().map(Position::from),
+            source,
+        }
+    }
+}
+
+impl ParseError {
+    /// Returns the name of the variant, e.g. `"InvalidNumber"`
+    pub fn kind(&self) -> &'static str {
+        use ParseError::*;
+        match self {
+            MissingColumn { .. } => "MissingColumn",
+            UnknownTypeTag { .. } => "UnknownTypeTag",
+            InvalidNumber { .. } => "InvalidNumber",
+            DataPointsMismatch { .. } => "DataPointsMismatch",
+            InvalidJson { .. } => "InvalidJson",
+            InvalidTxPayload { .. } => "InvalidTxPayload",
+            InvalidTimestamp(_) => "InvalidTimestamp",
+            NotEnoughSyncs { .. } => "NotEnoughSyncs",
+            NoTimeSyncMap(_) => "NoTimeSyncMap",
+            InvalidInfo(_) => "InvalidInfo",
+            InfoFile { .. } => "InfoFile",
+            Json(_) => "Json",
+            NoPackets => "NoPackets",
+            Csv { .. } => "Csv",
+            Io(_) => "Io",
+        }
+    }
+
+    /// Returns the record that caused this error, if any
+    pub fn record(&self) -> Option<&Record> {
+        use ParseError::*;
+        match self {
+            MissingColumn { record, .. }
+            | UnknownTypeTag { record, .. }
+            | InvalidNumber { record, .. }
+            | DataPointsMismatch { record, .. }
+            | InvalidJson { record, .. }
+            | InvalidTxPayload { record } => Some(record),
+            _ => None,
+        }
+    }
+
+    /// Returns the position of the record that caused this error, if known
+    pub fn position(&self) -> Option<&Position> {
+        match self {
+            ParseError::Csv { position, .. } => position.as_ref(),
+            _ => self.record().and_then(|r| r.position.as_ref()),
+        }
+    }
+
+    /// Records the file the failing record was read from
+    pub(crate) fn with_file(mut self, file: &Arc<Path>) -> Self {
+        use ParseError::*;
+        let position = match &mut self {
+            MissingColumn { record, .. }
+            | UnknownTypeTag { record, .. }
+            | InvalidNumber { record, .. }
+            | DataPointsMismatch { record, .. }
+            | InvalidJson { record, .. }
+            | InvalidTxPayload { record } => record.position.as_mut(),
+            Csv { position, .. } => position.as_mut(),
+            _ => None,
+        };
+        if let Some(position) = position {
+            position.file = Some(Arc::clone(file));
+        }
+        self
+    }
+}
@@ -0,0 +1,147 @@
+//! Export functions
+use crate::error::ParseError;
+use crate::recording::{Recording, RecordingSession};
+use crate::types::{
+    format_emotibit_time, format_host_time, packet_header, Csv, DataPacket, TimeSyncMap,
+};
+use crate::writer::{SplitWriter, WriterBuilder};
+use anyhow::Result;
+use csv::StringRecord;
+use std::{fs::File, io::Write, path::Path};
+
+/// Writes a recording as per-TypeTag CSV files named and laid out after those of the EmotiBit DataParser
+///
+/// The output has not been compared with the DataParser's, so it is not guaranteed to match it byte for byte.
+/// Creates `<stem>_ERROR.csv`, `<stem>_timesyncs.csv`, `<stem>_timeSyncMap.csv` and one `<stem>_<TAG>.csv` per TypeTag in `out_dir`,
+/// where `<stem>` is the file stem of the raw data. A recording with EmotiBit reboots gets one `timeSyncMap` row per session. Samples of multi-sample packets get their own timestamps,
+/// spread back from the packet timestamp at the nominal sampling rate, or the inferred one if there is no nominal rate.
+pub fn export_dataparser_layout<P: AsRef<Path>>(recording: &Recording, out_dir: P) -> Result<()> {
+    let out_dir = out_dir.as_ref();
+    let stem = recording
+        .file_path()
+        .and_then(|path| path.file_stem())
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("recording");
+    let path = |suffix: &str| out_dir.join(format!("{}_{}.csv", stem, suffix));
+
+    // Errors, as the raw lines that failed to parse where there is one
+    let mut output = File::create(path("ERROR"))?;
+    for err in recording.errors() {
+        match err.record() {
+            Some(record) => writeln!(output, "{}", record.raw)?,
+            None => writeln!(output, "{}", err)?,
+        }
+    }
+
+    // TimeSyncs and TimeSyncMaps of every session, or why there are none
+    let first_error = |error: fn(RecordingSession) -> Option<&ParseError>| {
+        let error = recording.sessions().find_map(error);
+        let message = error.map_or_else(|| ParseError::NoPackets.to_string(), |e| e.to_string());
+        StringRecord::from(vec![message])
+    };
+
+    let mut writer = WriterBuilder::new().from_path(path("timesyncs"))?;
+    let mut syncs = recording.time_syncs().peekable();
+    if syncs.peek().is_none() {
+        writer.write(&first_error(|session| session.time_syncs().err()))?;
+    }
+    for sync in syncs {
+        writer.write(sync)?;
+    }
+    writer.flush()?;
+
+    let mut writer = WriterBuilder::new().from_path(path("timeSyncMap"))?;
+    let mut maps = recording
+        .sessions()
+        .filter_map(|session| session.sync_map().ok())
+        .peekable();
+    if maps.peek().is_none() {
+        writer.write(&first_error(|session| session.sync_map().err()))?;
+    }
+    for map in maps {
+        writer.write(map)?;
+    }
+    writer.flush()?;
+
+    // Packets
+    let rates = recording.sampling_rates();
+    let mut writer = SplitWriter::new(out_dir, stem);
+    for session in recording.sessions() {
+        let sync_map = session.sync_map().ok();
+        for packet in session.packets() {
+            let sampling_rate = rates
+                .get(&packet.data_type.tag())
+                .copied()
+                .unwrap_or_default();
+            writer.write_tagged(
+                packet.data_type.tag(),
+                &SampleRows {
+                    packet,
+                    sampling_rate,
+                    sync_map,
+                },
+            )?;
+        }
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes the samples of a recording to a Parquet file, one row per sample
+///
+/// Samples are timestamped at the nominal sampling rate, or the inferred one if there is no nominal rate.
+/// See [`crate::parquet`] for the schema.
+#[cfg(feature = "parquet")]
+pub fn export_parquet<P: AsRef<Path>>(recording: &Recording, path: P) -> Result<()> {
+    let mut writer = crate::parquet::ParquetWriterBuilder::new()
+        .sampling_rates(recording.sampling_rates())
+        .from_path(path)?;
+    for packet in recording.packets() {
+        writer.write(packet)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// One row per sample with timestamps spread at `sampling_rate`, mapped to host time by the `TimeSyncMap` of the session
+struct SampleRows<'a> {
+    packet: &'a DataPacket,
+    sampling_rate: f64,
+    sync_map: Option<&'a TimeSyncMap>,
+}
+
+impl Csv for SampleRows<'_> {
+    fn csv(&self) -> Vec<StringRecord> {
+        let p = self.packet;
+        let values = DataPacket::parse_data_type(&p.data_type, p.data_type.payload());
+        let samples = p.samples(self.sampling_rate);
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let emotibit_timestamp = samples
+                    .get(i)
+                    .map_or(p.emotibit_timestamp, |s| s.emotibit_timestamp);
+                let host_timestamp = self
+                    .sync_map
+                    .map(|map| map.host_timestamp(emotibit_timestamp));
+                StringRecord::from(vec![
+                    host_timestamp.map_or_else(|| "NaN".to_owned(), format_host_time),
+                    format_emotibit_time(emotibit_timestamp),
+                    p.packet_id.to_string(),
+                    p.data_points.to_string(),
+                    p.data_type.as_str().to_owned(),
+                    p.version.to_string(),
+                    p.reliability.to_string(),
+                    value,
+                ])
+            })
+            .collect()
+    }
+
+    /// The payload column is named after the TypeTag, as in the DataParser
+    fn header(&self) -> Option<StringRecord> {
+        Some(packet_header(self.packet.data_type.tag().as_str()))
+    }
+}
@@ -0,0 +1,185 @@
+//! Recording info parsed from the `_info.json` sidecar
+use crate::error::{ParseError, Result};
+use crate::types::TypeTag;
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Recording info written by EmotiBit next to the raw CSV as `<timestamp>_info.json`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordingInfo {
+    /// EmotiBit device ID
+    pub device_id: Option<String>,
+    /// EmotiBit hardware version, e.g. `V04a`
+    pub hardware_version: Option<String>,
+    /// Feather board the EmotiBit is mounted on
+    pub feather_version: Option<String>,
+    /// Firmware version
+    pub firmware_version: Option<String>,
+    /// Firmware variant
+    pub firmware_variant: Option<String>,
+    /// Time the recording was created in `%Y-%m-%d_%H-%M-%S-f` format
+    pub created_at: Option<String>,
+    /// Sensors and their settings
+    pub sensors: Vec<SensorInfo>,
+}
+
+/// Settings of a single sensor stream
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SensorInfo {
+    /// Name of the stream, e.g. `EDA`
+    pub name: String,
+    /// Type of the stream
+    pub kind: Option<String>,
+    /// TypeTags carried by this stream
+    pub type_tags: Vec<String>,
+    /// Nominal sampling rate in Hz
+    pub sampling_rate: Option<f64>,
+    /// Number of channels
+    pub channel_count: Option<u32>,
+    /// Format of the channel values, e.g. `float`
+    pub channel_format: Option<String>,
+    /// Physical unit of the values
+    pub units: Option<String>,
+    /// Remaining sensor-specific settings
+    pub setup: BTreeMap<String, String>,
+}
+
+impl SensorInfo {
+    /// Returns the TypeTags of this stream that are known to this crate
+    pub fn tags(&self) -> impl Iterator<Item = TypeTag> + '_ {
+        self.type_tags.iter().filter_map(|tag| tag.parse().ok())
+    }
+}
+
+impl RecordingInfo {
+    /// Reads an `_info.json` file
+    pub fn from_path<T: AsRef<Path>>(file_path: T) -> Result<Self> {
+        fs::read_to_string(file_path)?.parse()
+    }
+
+    /// Returns the info of the sensor stream carrying `tag`
+    pub fn sensor(&self, tag: TypeTag) -> Option<&SensorInfo> {
+        self.sensors
+            .iter()
+            .find(|sensor| sensor.tags().any(|t| t == tag))
+    }
+}
+
+impl FromStr for RecordingInfo {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value: Value = serde_json::from_str(s)?;
+        let entries: Vec<&Map<String, Value>> = match &value {
+            Value::Array(entries) => entries.iter().filter_map(info_object).collect(),
+            value => info_object(value).into_iter().collect(),
+        };
+        if entries.is_empty() {
+            return Err(ParseError::InvalidInfo(
+                "expected an array of info objects".to_owned(),
+            ));
+        }
+
+        let find = |key| entries.iter().find_map(|entry| get_string(entry, key));
+        Ok(RecordingInfo {
+            device_id: find("emotibit_device_id"),
+            hardware_version: find("hardware_version"),
+            feather_version: find("feather_version"),
+            firmware_version: find("firmware_version"),
+            firmware_variant: find("firmware_variant"),
+            created_at: find("created_at"),
+            sensors: entries
+                .iter()
+                .filter_map(|entry| sensor_info(entry))
+                .collect(),
+        })
+    }
+}
+
+/// Returns the path of the `_info.json` sidecar of a raw CSV file
+pub fn sidecar_path<T: AsRef<Path>>(file_path: T) -> PathBuf {
+    let file_path = file_path.as_ref();
+    let stem = file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    file_path.with_file_name(format!("{}_info.json", stem))
+}
+
+/// Reads the `_info.json` sidecar of a raw CSV file. Returns `None` if there is no sidecar.
+pub fn read_sidecar<T: AsRef<Path>>(file_path: T) -> Result<Option<RecordingInfo>> {
+    let sidecar = sidecar_path(file_path);
+    if !sidecar.exists() {
+        return Ok(None);
+    }
+    RecordingInfo::from_path(&sidecar)
+        .map(Some)
+        .map_err(|source| ParseError::InfoFile {
+            path: sidecar,
+            source: Box::new(source),
+        })
+}
+
+// Each entry is either `{"info": {...}}` or the info object itself
+fn info_object(value: &Value) -> Option<&Map<String, Value>> {
+    let object = value.as_object()?;
+    match object.get("info") {
+        Some(Value::Object(info)) => Some(info),
+        _ => Some(object),
+    }
+}
+
+fn sensor_info(entry: &Map<String, Value>) -> Option<SensorInfo> {
+    let type_tags: Vec<String> = entry.get("typeTags").map(|tags| match tags {
+        Value::Array(tags) => tags.iter().filter_map(as_string).collect(),
+        tag => as_string(tag).into_iter().collect(),
+    })?;
+
+    let setup = match entry.get("setup") {
+        Some(Value::Object(setup)) => setup
+            .iter()
+            .filter_map(|(key, value)| Some((key.to_owned(), as_string(value)?)))
+            .collect(),
+        _ => BTreeMap::new(),
+    };
+
+    Some(SensorInfo {
+        name: get_string(entry, "name").unwrap_or_default(),
+        kind: get_string(entry, "type"),
+        type_tags,
+        sampling_rate: entry.get("nominal_srate").and_then(as_f64),
+        channel_count: entry
+            .get("channel_count")
+            .and_then(as_f64)
+            .map(|n| n as u32),
+        channel_format: get_string(entry, "channel_format"),
+        units: get_string(entry, "units"),
+        setup,
+    })
+}
+
+fn get_string(entry: &Map<String, Value>, key: &str) -> Option<String> {
+    entry.get(key).and_then(as_string)
+}
+
+// Values are written as strings or numbers depending on the firmware version
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.to_owned()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
@@ -1,4 +1,16 @@
+pub mod analysis;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod edf;
+pub mod error;
+pub mod export;
+pub mod info;
+pub mod metadata;
+#[cfg(feature = "parquet")]
+pub mod parquet;
 pub mod parser;
+pub mod recording;
+pub mod summary;
 pub mod types;
 pub mod writer;
 pub use csv;
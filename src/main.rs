@@ -0,0 +1,293 @@
+//! Command-line interface for raw EmotiBit data
+//!
+//! Exit codes: 0 on success, 1 if some records could not be parsed, 2 on fatal errors.
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(feature = "parquet")]
+use emotibit_data::parquet::ParquetWriterBuilder;
+use emotibit_data::{
+    analysis,
+    edf::{EdfFormat, EdfWriterBuilder},
+    export,
+    parser::{PacketReader, Validation},
+    recording::Recording,
+    summary::RecordingSummary,
+    types::DataPacket,
+    writer::WriterBuilder,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Parses raw EmotiBit data")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Writes per-TypeTag CSV files named and laid out after those of the EmotiBit DataParser
+    Parse {
+        /// Raw data file
+        input: PathBuf,
+        /// Output directory. Defaults to the directory of the input.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Prints a summary of a raw data file
+    Info {
+        /// Raw data file
+        input: PathBuf,
+        /// Prints the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Reports records that fail to parse, data_points mismatches and packet loss
+    Validate {
+        /// Raw data file
+        input: PathBuf,
+    },
+    /// Converts packets to another format
+    Convert {
+        /// Raw data file
+        input: PathBuf,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// Output file, or `-` for stdout. Defaults to `<input stem>_packets.<format>` next to the input.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// One row per value
+    Csv,
+    /// One JSON object per packet and line
+    Json,
+    /// EDF+ with 16-bit samples resampled onto 1 s data records, one file per session
+    Edf,
+    /// BDF+ with 24-bit samples resampled onto 1 s data records, one file per session
+    Bdf,
+    /// One row per sample, ZSTD compressed
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "jsonl",
+            Format::Edf => "edf",
+            Format::Bdf => "bdf",
+            #[cfg(feature = "parquet")]
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+/// Result of a command that did not fail fatally
+enum Outcome {
+    Success,
+    PartialErrors,
+}
+
+impl Outcome {
+    fn from_error_count(count: usize) -> Self {
+        if count == 0 {
+            Outcome::Success
+        } else {
+            Outcome::PartialErrors
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(Outcome::Success) => ExitCode::SUCCESS,
+        Ok(Outcome::PartialErrors) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(command: Command) -> Result<Outcome> {
+    match command {
+        Command::Parse { input, output } => parse(&input, output),
+        Command::Info { input, json } => info(&input, json),
+        Command::Validate { input } => validate(&input),
+        Command::Convert {
+            input,
+            format,
+            output,
+        } => convert(&input, format, output),
+    }
+}
+
+fn parse(input: &Path, output: Option<PathBuf>) -> Result<Outcome> {
+    let recording = Recording::open(input)?;
+    let out_dir = output.unwrap_or_else(|| input.parent().unwrap_or(Path::new(".")).to_owned());
+    export::export_dataparser_layout(&recording, out_dir)?;
+    report_info_error(&recording);
+    for err in recording.errors() {
+        eprintln!("{}", err);
+    }
+    for warning in recording.warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(Outcome::from_error_count(recording.errors().len()))
+}
+
+fn info(input: &Path, json: bool) -> Result<Outcome> {
+    let recording = Recording::open(input)?;
+    report_info_error(&recording);
+    let summary = RecordingSummary::new(&recording);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary.to_json())?);
+    } else {
+        println!("File: {}", input.display());
+        print!("{}", summary);
+    }
+    Ok(Outcome::from_error_count(recording.errors().len()))
+}
+
+/// Prints why the info sidecar could not be read. The recording is still usable without it.
+fn report_info_error(recording: &Recording) {
+    if let Some(e) = recording.info_error() {
+        eprintln!("warning: {}", e);
+    }
+}
+
+fn validate(input: &Path) -> Result<Outcome> {
+    let reader = PacketReader::from_path(input)?.validation(Validation::Warn);
+    let mut packets = vec![];
+    let mut errors = 0;
+    let mut warnings = 0;
+    for result in reader {
+        match result {
+            Ok(packet) => {
+                if let Some(warning) = &packet.warning {
+                    warnings += 1;
+                    println!("warning: {}", warning);
+                }
+                packets.push(packet);
+            }
+            Err(e) => {
+                errors += 1;
+                println!("error: {}", e);
+            }
+        }
+    }
+
+    let loss = analysis::packet_loss(&packets);
+    println!(
+        "{} packets, {} errors, {} warnings, {} missing ({:.2}%), {} duplicates, {} out of order, {} resets",
+        packets.len(),
+        errors,
+        warnings,
+        loss.missing.len(),
+        loss.loss_ratio() * 100.0,
+        loss.duplicates.len(),
+        loss.out_of_order.len(),
+        loss.resets.len(),
+    );
+    Ok(Outcome::from_error_count(errors + warnings))
+}
+
+fn convert(input: &Path, format: Format, output: Option<PathBuf>) -> Result<Outcome> {
+    let output = output.unwrap_or_else(|| {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        input.with_file_name(format!("{}_packets.{}", stem, format.extension()))
+    });
+
+    let mut errors = 0;
+    let mut packets = PacketReader::from_path(input)?.filter_map(|result| match result {
+        Ok(packet) => Some(packet),
+        Err(e) => {
+            errors += 1;
+            eprintln!("{}", e);
+            None
+        }
+    });
+    match format {
+        Format::Csv => {
+            let mut writer = WriterBuilder::new().from_writer(create_output(&output)?);
+            packets.try_for_each(|packet| writer.write(&packet))?;
+            writer.flush()?;
+        }
+        Format::Json => {
+            let mut writer = BufWriter::new(create_output(&output)?);
+            packets.try_for_each(|packet: DataPacket| -> Result<()> {
+                serde_json::to_writer(&mut writer, &packet)?;
+                writeln!(writer)?;
+                Ok(())
+            })?;
+            writer.flush()?;
+        }
+        Format::Edf => return write_edf(input, EdfFormat::Edf, &output),
+        Format::Bdf => return write_edf(input, EdfFormat::Bdf, &output),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => {
+            let mut writer = ParquetWriterBuilder::new().from_writer(create_output(&output)?)?;
+            packets.try_for_each(|packet| writer.write(&packet))?;
+            writer.finish()?.flush()?;
+        }
+    }
+    Ok(Outcome::from_error_count(errors))
+}
+
+/// Creates the output file, or returns stdout for `-`
+fn create_output(path: &Path) -> Result<Box<dyn Write + Send>> {
+    Ok(if path == Path::new("-") {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::create(path)?)
+    })
+}
+
+/// Writes a recording with its info sidecar, if any, as EDF+ or BDF+
+///
+/// EmotiBit time starts over on a reboot, so a recording with several sessions is written to
+/// `<output stem>_<n>.<ext>`, one file per session.
+fn write_edf(input: &Path, format: EdfFormat, output: &Path) -> Result<Outcome> {
+    let recording = Recording::open(input)?;
+    report_info_error(&recording);
+    for err in recording.errors() {
+        eprintln!("{}", err);
+    }
+    let builder = || EdfWriterBuilder::new().format(format);
+    let sessions = recording.sessions().len();
+    if sessions <= 1 {
+        builder()
+            .from_writer(create_output(output)?)
+            .write(&recording)?
+            .flush()?;
+    } else if output == Path::new("-") {
+        bail!(
+            "{} has {} sessions between EmotiBit reboots, which need one file each",
+            input.display(),
+            sessions
+        );
+    } else {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = output.extension().unwrap_or_default().to_string_lossy();
+        for (i, session) in recording.sessions().enumerate() {
+            let path = output.with_file_name(format!("{}_{}.{}", stem, i + 1, extension));
+            builder()
+                .from_path(&path)?
+                .write_session(&recording, session)?
+                .flush()?;
+            eprintln!("Session {}: {}", i + 1, path.display());
+        }
+    }
+    Ok(Outcome::from_error_count(recording.errors().len()))
+}
@@ -0,0 +1,218 @@
+//! Metadata of TypeTags
+use crate::info::RecordingInfo;
+use crate::types::{DataType, TypeTag};
+use std::{borrow::Cow, collections::BTreeMap};
+
+/// Sensor group a TypeTag belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SensorGroup {
+    /// Electrodermal activity
+    Eda,
+    /// Photoplethysmography
+    Ppg,
+    /// Accelerometer, gyroscope and magnetometer
+    Imu,
+    /// Temperature and humidity
+    Thermal,
+    /// Battery status
+    Battery,
+    /// Values computed on the EmotiBit, such as heart rate
+    Derived,
+    /// Control and computer messages
+    Control,
+    /// TypeTags unknown to this crate
+    Other,
+}
+
+/// Type of the values in a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    /// `f32`
+    Float,
+    /// `u32`
+    UnsignedInt,
+    /// `i32`
+    SignedInt,
+    /// `String`
+    Text,
+}
+
+/// Metadata of a TypeTag
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    /// Human-readable name
+    pub name: Cow<'static, str>,
+    /// Physical unit of the values, if any
+    pub unit: Option<Cow<'static, str>>,
+    /// Nominal sampling rate in Hz, `None` for event-based TypeTags
+    pub sampling_rate: Option<f64>,
+    /// Smallest and largest value the sensor can report in `unit`, if fixed by the hardware
+    pub physical_range: Option<(f64, f64)>,
+    /// Sensor group
+    pub group: SensorGroup,
+    /// Type of the values in the payload
+    pub value_type: ValueType,
+}
+
+impl TypeTag {
+    /// Returns the built-in metadata of this TypeTag
+    pub fn metadata(&self) -> Metadata {
+        use SensorGroup::*;
+        use TypeTag::*;
+        use ValueType::*;
+        let (name, unit, sampling_rate, group, value_type) = match self {
+            EA => ("EDA", Some("uS"), Some(15.0), Eda, Float),
+            EL => ("EDL", Some("uS"), Some(15.0), Eda, Float),
+            ER => ("EDR", Some("uS"), Some(15.0), Eda, Float),
+            PI => ("PPG Infrared", Some("counts"), Some(25.0), Ppg, UnsignedInt),
+            PR => ("PPG Red", Some("counts"), Some(25.0), Ppg, UnsignedInt),
+            PG => ("PPG Green", Some("counts"), Some(25.0), Ppg, UnsignedInt),
+            T0 => ("Temperature 0", Some("degC"), Some(7.5), Thermal, Float),
+            T1 => ("Temperature 1", Some("degC"), Some(7.5), Thermal, Float),
+            TH => ("Thermopile", Some("degC"), Some(7.5), Thermal, Float),
+            H0 => ("Humidity 0", Some("%RH"), Some(7.5), Thermal, Float),
+            AX => ("Accelerometer X", Some("g"), Some(25.0), Imu, Float),
+            AY => ("Accelerometer Y", Some("g"), Some(25.0), Imu, Float),
+            AZ => ("Accelerometer Z", Some("g"), Some(25.0), Imu, Float),
+            GX => ("Gyroscope X", Some("deg/s"), Some(25.0), Imu, Float),
+            GY => ("Gyroscope Y", Some("deg/s"), Some(25.0), Imu, Float),
+            GZ => ("Gyroscope Z", Some("deg/s"), Some(25.0), Imu, Float),
+            MX => ("Magnetometer X", Some("uT"), Some(25.0), Imu, SignedInt),
+            MY => ("Magnetometer Y", Some("uT"), Some(25.0), Imu, SignedInt),
+            MZ => ("Magnetometer Z", Some("uT"), Some(25.0), Imu, SignedInt),
+            BV => ("Battery Voltage", Some("V"), None, Battery, Float),
+            BATLV => ("Battery Percentage", Some("%"), None, Battery, UnsignedInt),
+            AK => ("Acknowledge", None, None, Control, Text),
+            RD => ("Request Data", None, None, Control, Text),
+            TL => ("Timestamp Local", None, None, Control, Text),
+            TX => ("Transmit", None, None, Control, Text),
+            TxTlLc => ("Timestamp Local and LSL Clock", None, None, Control, Text),
+            TxLcLm => ("LSL Clock and LSL Marker", None, None, Control, Float),
+            EM => ("EmotiBit Mode", None, None, Control, Text),
+            HR => ("Heart Rate", Some("bpm"), None, Derived, SignedInt),
+            BI => ("Inter-beat Interval", Some("ms"), None, Derived, SignedInt),
+            SA => ("SCR Amplitude", Some("uS"), None, Derived, Float),
+            SF => ("SCR Frequency", Some("1/min"), None, Derived, Float),
+            SR => ("SCR Rise Time", Some("s"), None, Derived, Float),
+            UN => ("User Note", None, None, Control, Text),
+            LM => ("LSL Marker", None, None, Control, Text),
+            RB => ("Record Begin", None, None, Control, Text),
+            RS => ("Record Stop", None, None, Control, Text),
+            DC => ("Data Clipping", None, None, Control, Text),
+            DO => ("Data Overflow", None, None, Control, Text),
+            HE => ("Hello EmotiBit", None, None, Control, Text),
+            HH => ("Hello Host", None, None, Control, Text),
+            PN => ("Ping", None, None, Control, Text),
+            PO => ("Pong", None, None, Control, Text),
+            EC => ("EmotiBit Connect", None, None, Control, Text),
+            ED => ("EmotiBit Disconnect", None, None, Control, Text),
+            ML => ("Mode Low Power", None, None, Control, Text),
+            MN => ("Mode Normal Power", None, None, Control, Text),
+            MH => ("Mode Hibernate", None, None, Control, Text),
+            MO => ("Mode Wireless Off", None, None, Control, Text),
+            EI => ("EmotiBit Info", None, None, Control, Text),
+            Unknown => ("Unknown", None, None, Other, Text),
+        };
+        // Ranges of the EmotiBit V4 sensors at their default settings
+        let physical_range = match self {
+            EA | EL => Some((0.0, 100.0)),
+            PI | PR | PG => Some((0.0, 262143.0)),
+            T0 | T1 | TH => Some((-40.0, 125.0)),
+            H0 | BATLV => Some((0.0, 100.0)),
+            AX | AY | AZ => Some((-8.0, 8.0)),
+            GX | GY | GZ => Some((-1000.0, 1000.0)),
+            MX | MY => Some((-1300.0, 1300.0)),
+            MZ => Some((-2500.0, 2500.0)),
+            BV => Some((0.0, 5.0)),
+            _ => None,
+        };
+        Metadata {
+            name: Cow::Borrowed(name),
+            unit: unit.map(Cow::Borrowed),
+            sampling_rate,
+            physical_range,
+            group,
+            value_type,
+        }
+    }
+}
+
+impl DataType {
+    /// Returns the built-in metadata of the TypeTag of this data type
+    pub fn metadata(&self) -> Metadata {
+        self.tag().metadata()
+    }
+}
+
+/// Metadata of every TypeTag, starting from the built-in values and overridable per recording
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataTable {
+    overrides: BTreeMap<TypeTag, Metadata>,
+}
+
+impl MetadataTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a `MetadataTable` with the sampling rates and units of a recording's info file
+    pub fn from_info(info: &RecordingInfo) -> Self {
+        let mut table = Self::new();
+        for sensor in &info.sensors {
+            for tag in sensor.tags() {
+                if let Some(rate) = sensor.sampling_rate.filter(|rate| *rate > 0.0) {
+                    table.set_sampling_rate(tag, rate);
+                }
+                if let Some(unit) = sensor
+                    .units
+                    .as_ref()
+                    .filter(|u| !u.is_empty() && *u != "N/A")
+                {
+                    table.set_unit(tag, unit.to_owned());
+                }
+            }
+        }
+        table
+    }
+
+    /// Returns the metadata of `tag`
+    pub fn get(&self, tag: TypeTag) -> Metadata {
+        self.overrides
+            .get(&tag)
+            .cloned()
+            .unwrap_or_else(|| tag.metadata())
+    }
+
+    /// Replaces the metadata of `tag`
+    pub fn set(&mut self, tag: TypeTag, metadata: Metadata) {
+        self.overrides.insert(tag, metadata);
+    }
+
+    /// Overrides the nominal sampling rate of `tag`
+    pub fn set_sampling_rate(&mut self, tag: TypeTag, sampling_rate: f64) {
+        let mut metadata = self.get(tag);
+        metadata.sampling_rate = Some(sampling_rate);
+        self.set(tag, metadata);
+    }
+
+    /// Overrides the physical unit of `tag`
+    pub fn set_unit<T: Into<Cow<'static, str>>>(&mut self, tag: TypeTag, unit: T) {
+        let mut metadata = self.get(tag);
+        metadata.unit = Some(unit.into());
+        self.set(tag, metadata);
+    }
+
+    /// Overrides the physical range of `tag`, e.g. for a sensor configured with a different range
+    pub fn set_physical_range(&mut self, tag: TypeTag, min: f64, max: f64) {
+        let mut metadata = self.get(tag);
+        metadata.physical_range = Some((min, max));
+        self.set(tag, metadata);
+    }
+
+    /// Returns the nominal sampling rates of all TypeTags that have one
+    pub fn sampling_rates(&self) -> BTreeMap<TypeTag, f64> {
+        TypeTag::all()
+            .filter_map(|tag| Some((tag, self.get(tag).sampling_rate?)))
+            .collect()
+    }
+}
@@ -0,0 +1,216 @@
+//! Apache Parquet export
+//!
+//! Packets are written in long format, one row per sample, with the columns of [`crate::arrow::sample_schema`]:
+//! `host_timestamp`, `emotibit_timestamp`, `packet_id`, `type_tag` and `value`.
+//!
+//! Packets without numeric samples, such as `TL`, `RB` or `UN`, are left out.
+use crate::arrow::{sample_schema, SampleColumns};
+use crate::metadata::MetadataTable;
+use crate::types::{DataPacket, TypeTag};
+use ::parquet::{arrow::ArrowWriter, file::properties::WriterProperties};
+use anyhow::Result;
+use std::{collections::BTreeMap, fs::File, io::Write, path::Path};
+
+pub use ::parquet::basic::{Compression, ZstdLevel};
+
+/// Number of rows buffered before a `RecordBatch` is handed to the Parquet writer
+const DEFAULT_BATCH_SIZE: usize = 64 * 1024;
+
+/// Use `ParquetWriterBuilder` to build this struct.
+pub struct ParquetWriter<W: Write + Send = File> {
+    writer: ArrowWriter<W>,
+    columns: SampleColumns,
+    sampling_rates: BTreeMap<TypeTag, f64>,
+    batch_size: usize,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Writes the samples of a `DataPacket`
+    ///
+    /// Samples are spread back from the packet timestamp at the sampling rate of its TypeTag.
+    pub fn write(&mut self, packet: &DataPacket) -> Result<()> {
+        let sampling_rate = self
+            .sampling_rates
+            .get(&packet.data_type.tag())
+            .copied()
+            .unwrap_or_default();
+        self.columns.append(packet, sampling_rate);
+        if self.columns.len() >= self.batch_size {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Writes buffered rows and the file footer, and returns the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.write_batch()?;
+        Ok(self.writer.into_inner()?)
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        if self.columns.len() > 0 {
+            self.writer.write(&self.columns.finish()?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builder struct for `ParquetWriter`
+pub struct ParquetWriterBuilder {
+    compression: Compression,
+    batch_size: usize,
+    sampling_rates: BTreeMap<TypeTag, f64>,
+}
+
+impl Default for ParquetWriterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParquetWriterBuilder {
+    pub fn new() -> Self {
+        ParquetWriterBuilder {
+            compression: Compression::ZSTD(ZstdLevel::default()),
+            batch_size: DEFAULT_BATCH_SIZE,
+            sampling_rates: MetadataTable::new().sampling_rates(),
+        }
+    }
+    /// Sets the compression codec. Defaults to ZSTD.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+    /// Sets the number of rows buffered before they are handed to the Parquet writer
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+    /// Sets sampling rates in Hz used to timestamp samples. Defaults to the nominal rates.
+    ///
+    /// TypeTags without a rate get the packet timestamp for all of their samples.
+    pub fn sampling_rates(mut self, sampling_rates: BTreeMap<TypeTag, f64>) -> Self {
+        self.sampling_rates = sampling_rates;
+        self
+    }
+    /// Creates `ParquetWriter` with a file path
+    pub fn from_path<P: AsRef<Path>>(self, path: P) -> Result<ParquetWriter> {
+        self.from_writer(File::create(path)?)
+    }
+    /// Creates `ParquetWriter` with any `io::Write`
+    pub fn from_writer<W: Write + Send>(self, writer: W) -> Result<ParquetWriter<W>> {
+        let properties = WriterProperties::builder()
+            .set_compression(self.compression)
+            .build();
+        let schema = sample_schema();
+        Ok(ParquetWriter {
+            writer: ArrowWriter::try_new(writer, schema.clone(), Some(properties))?,
+            columns: SampleColumns::new(schema),
+            sampling_rates: self.sampling_rates,
+            batch_size: self.batch_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use arrow_array::{
+        cast::AsArray,
+        types::{Float64Type, UInt32Type},
+        RecordBatch,
+    };
+
+    const HOST_TIME: f64 = 1664362000.0;
+
+    fn packet(line: &str) -> DataPacket {
+        let mut packet = DataPacket::try_from(line).unwrap();
+        packet.host_timestamp = Some(HOST_TIME + packet.emotibit_timestamp / 1000.0);
+        packet
+    }
+
+    #[test]
+    fn reads_back_one_row_per_numeric_sample() {
+        let path = std::env::temp_dir().join(format!(
+            "emotibit-data-parquet-{}.parquet",
+            std::process::id()
+        ));
+        let mut writer = ParquetWriterBuilder::new()
+            .batch_size(4)
+            .from_path(&path)
+            .unwrap();
+
+        writer
+            .write(&packet("1000,1,3,EA,1,100,0.1,0.2,0.3"))
+            .unwrap();
+        writer
+            .write(&packet("1050,2,1,TL,1,100,2022-09-28_10-46-20-000000"))
+            .unwrap();
+        assert_eq!(writer.columns.len(), 3);
+        assert_eq!(writer.writer.in_progress_rows(), 0);
+        // The fifth row reaches the batch size and hands the rows to the Parquet writer
+        writer
+            .write(&packet("1100,3,2,PI,1,100,156593,156471"))
+            .unwrap();
+        assert_eq!(writer.columns.len(), 0);
+        assert_eq!(writer.writer.in_progress_rows(), 5);
+        writer.write(&packet("1200,4,1,UN,1,100,note")).unwrap();
+        writer.write(&packet("1300,5,1,BV,1,100,3.7")).unwrap();
+        writer.finish().unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.schema().fields(), sample_schema().fields());
+        let batches: Vec<RecordBatch> = reader.build().unwrap().map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        let column = |i: usize| -> Vec<f64> {
+            batches
+                .iter()
+                .flat_map(|b| b.column(i).as_primitive::<Float64Type>().values().to_vec())
+                .collect()
+        };
+        let packet_ids: Vec<u32> = batches
+            .iter()
+            .flat_map(|b| b.column(2).as_primitive::<UInt32Type>().values().to_vec())
+            .collect();
+        let type_tags: Vec<String> = batches
+            .iter()
+            .flat_map(|b| {
+                b.column(3)
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|s| s.unwrap().to_owned())
+            })
+            .collect();
+
+        // TL and UN have no numeric samples and are left out
+        assert_eq!(type_tags, ["EA", "EA", "EA", "PI", "PI", "BV"]);
+        assert_eq!(packet_ids, [1, 1, 1, 3, 3, 5]);
+        assert_eq!(
+            column(4),
+            [
+                0.1f32 as f64,
+                0.2f32 as f64,
+                0.3f32 as f64,
+                156593.0,
+                156471.0,
+                3.7f32 as f64
+            ]
+        );
+
+        // Samples are spread back from the packet timestamp at the nominal rates of 15 Hz and 25 Hz
+        let expected = [
+            1000.0 - 2000.0 / 15.0,
+            1000.0 - 1000.0 / 15.0,
+            1000.0,
+            1060.0,
+            1100.0,
+            1300.0,
+        ];
+        for (emotibit, (host, expected)) in column(1).iter().zip(column(0).iter().zip(expected)) {
+            assert!((emotibit - expected).abs() < 1e-9);
+            assert!((host - (HOST_TIME + expected / 1000.0)).abs() < 1e-6);
+        }
+    }
+}
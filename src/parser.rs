@@ -1,38 +1,190 @@
 //! Parser functions
-use crate::types::{DataPacket, DataType, TimeSync, TimeSyncMap};
-use anyhow::{anyhow, Result};
+use crate::analysis;
+use crate::error::{ParseError, Record, Result, Warning};
+use crate::info::{self, RecordingInfo};
+use crate::types::{DataPacket, DataType, Session, TimeSync, TimeSyncMap};
 use chrono::{offset::TimeZone, DateTime, Local, NaiveDateTime};
-use csv::ReaderBuilder;
+use csv::{ReaderBuilder, StringRecord, StringRecordsIntoIter};
 use itertools::izip;
-use std::path::Path;
+use std::{fs::File, io::Read, path::Path, sync::Arc};
 
 const PARSER_VERSION: &str = "0.1.0";
 const MIN_SYNCS_REQUIRED: usize = 3;
 
+/// How the parser treats TypeTags unknown to this crate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Unknown TypeTags are errors
+    #[default]
+    Strict,
+    /// Unknown TypeTags are kept as `DataType::Unknown`
+    Lenient,
+}
+
+/// How the parser reacts when `data_points` does not match the number of payload values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// The packet is replaced by a `ParseError::DataPointsMismatch`
+    Error,
+    /// The packet is kept and the mismatch is recorded in `DataPacket::warning`
+    #[default]
+    Warn,
+    /// The packet is kept as is
+    Ignore,
+}
+
 /// Reads a csv file and creates `DataPacket`s
+///
+/// Every record of the file results in either a packet or an error, so one bad record does not discard the rest.
 pub fn get_packets<T: AsRef<Path>>(file_path: T) -> Result<Vec<Result<DataPacket>>> {
-    let mut vec: Vec<Result<DataPacket>> = Vec::new();
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(false)
-        .from_path(file_path)?;
-    for record in reader.records() {
-        match DataPacket::try_from(record.as_ref().unwrap()) {
-            Ok(packet) => vec.push(Ok(split_tx_or_as_is(packet)?)),
-            Err(e) => vec.push(Err(anyhow!("{}, record: {:?}", e, record))),
+    Ok(PacketReader::from_path(file_path)?.collect())
+}
+
+/// Results of the records of a csv file, and the outcome of reading its `_info.json` sidecar
+pub type PacketsWithInfo = (Vec<Result<DataPacket>>, Result<Option<RecordingInfo>>);
+
+/// Reads a csv file and its `_info.json` sidecar. The info is `Ok(None)` if there is no sidecar.
+///
+/// A sidecar that cannot be read does not discard the packets, so its outcome is returned next to them.
+pub fn get_packets_with_info<T: AsRef<Path>>(file_path: T) -> Result<PacketsWithInfo> {
+    Ok((get_packets(&file_path)?, info::read_sidecar(&file_path)))
+}
+
+/// Reads raw EmotiBit data from any `io::Read` and yields `DataPacket`s one at a time
+pub struct PacketReader<R> {
+    records: StringRecordsIntoIter<R>,
+    file: Option<Arc<Path>>,
+    mode: Mode,
+    validation: Validation,
+    done: bool,
+}
+
+impl<R: Read> PacketReader<R> {
+    /// Creates a `PacketReader` from a reader such as stdin, a socket or an in-memory buffer
+    pub fn new(reader: R) -> Self {
+        PacketReader {
+            records: ReaderBuilder::new()
+                .flexible(true)
+                .has_headers(false)
+                .quoting(false)
+                .from_reader(reader)
+                .into_records(),
+            file: None,
+            mode: Mode::Strict,
+            validation: Validation::Warn,
+            done: false,
         }
     }
-    Ok(vec)
+
+    /// Sets how unknown TypeTags are treated. Defaults to `Mode::Strict`.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets how a `data_points` value that does not match the payload length is reported. Defaults to `Validation::Warn`.
+    pub fn validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Sets the file name reported in the positions of packets and errors
+    pub fn with_file<T: AsRef<Path>>(mut self, file_path: T) -> Self {
+        self.file = Some(Arc::from(file_path.as_ref()));
+        self
+    }
+
+    fn locate(&self, e: ParseError) -> ParseError {
+        match &self.file {
+            Some(file) => e.with_file(file),
+            None => e,
+        }
+    }
+}
+
+impl PacketReader<File> {
+    /// Creates a `PacketReader` with a file path
+    pub fn from_path<T: AsRef<Path>>(file_path: T) -> Result<Self> {
+        Ok(Self::new(File::open(&file_path)?).with_file(file_path))
+    }
+}
+
+impl<R: Read> Iterator for PacketReader<R> {
+    type Item = Result<DataPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(e) => {
+                // An I/O error leaves the underlying reader in an unknown state
+                self.done = e.is_io_error();
+                return Some(Err(self.locate(e.into())));
+            }
+        };
+        let result = DataPacket::from_record(&record, self.mode)
+            .and_then(|packet| split_tx_or_as_is(packet, &record));
+        Some(match result {
+            Ok(mut packet) => {
+                if let (Some(file), Some(position)) = (&self.file, packet.position.as_mut()) {
+                    position.file = Some(Arc::clone(file));
+                }
+                match check_data_points(&packet, &record) {
+                    Some(warning) if self.validation == Validation::Error => {
+                        Err(self.locate(warning.into()))
+                    }
+                    Some(warning) if self.validation == Validation::Warn => {
+                        packet.warning = Some(Box::new(warning));
+                        Ok(packet)
+                    }
+                    _ => Ok(packet),
+                }
+            }
+            Err(e) => Err(self.locate(e)),
+        })
+    }
+}
+
+fn check_data_points(packet: &DataPacket, record: &StringRecord) -> Option<Warning> {
+    use DataType::*;
+    // Free text and JSON may contain commas, and split TX packets drop their TypeTags,
+    // so their payload length says nothing about `data_points`
+    if matches!(
+        packet.data_type,
+        TL(_) | LM(_) | RB(_) | RS(_) | UN(_) | EI(_) | TxTlLc(_) | TxLcLm(_)
+    ) {
+        return None;
+    }
+    let actual = packet.data_type.payload().len();
+    (actual != packet.data_points as usize).then(|| Warning::DataPointsMismatch {
+        expected: packet.data_points,
+        actual,
+        record: Record {
+            position: packet.position.clone(),
+            raw: Record::from(record).raw,
+        },
+    })
 }
 
-fn split_tx_or_as_is(x: DataPacket) -> Result<DataPacket> {
+fn split_tx_or_as_is(x: DataPacket, record: &StringRecord) -> Result<DataPacket> {
     if let DataType::TX(data) = &x.data_type {
         if let (Some(tag1), Some(val1), Some(tag2), Some(val2)) =
-            (data.get(0), data.get(1), data.get(2), data.get(3))
+            (data.first(), data.get(1), data.get(2), data.get(3))
         {
+            let invalid = |_| ParseError::InvalidTxPayload {
+                record: record.into(),
+            };
             let data_type = match (tag1.as_ref(), tag2.as_ref()) {
-                ("LC", "LM") => Some(DataType::TxLcLm(vec![val1.parse()?, val2.parse()?])),
-                ("TL", "LC") => Some(DataType::TxTlLc((val1.to_owned(), val2.parse()?))),
+                ("LC", "LM") => Some(DataType::TxLcLm((
+                    val1.parse().map_err(invalid)?,
+                    val2.parse().map_err(invalid)?,
+                ))),
+                ("TL", "LC") => Some(DataType::TxTlLc((
+                    val1.to_owned(),
+                    val2.parse().map_err(invalid)?,
+                ))),
                 _ => None,
             };
 
@@ -45,9 +197,13 @@ fn split_tx_or_as_is(x: DataPacket) -> Result<DataPacket> {
                     version: x.version,
                     reliability: x.reliability,
                     data_type,
+                    position: x.position,
+                    warning: x.warning,
                 });
             }
-            return Err(anyhow!("Invalid data"));
+            return Err(ParseError::InvalidTxPayload {
+                record: record.into(),
+            });
         }
     }
     Ok(x)
@@ -55,20 +211,24 @@ fn split_tx_or_as_is(x: DataPacket) -> Result<DataPacket> {
 
 /// Finds blocks of RD, TL, and AK and creates `TimeSync`s
 pub fn find_syncs(packets: &[Result<DataPacket>]) -> Result<Vec<TimeSync>> {
+    syncs_of(packets.iter().filter_map(|result| result.as_ref().ok()))
+}
+
+pub(crate) fn syncs_of<'a, I>(packets: I) -> Result<Vec<TimeSync>>
+where
+    I: Iterator<Item = &'a DataPacket>,
+{
     use DataType::*;
     let mut vec = vec![];
     let syncs: Vec<&DataPacket> = packets
-        .iter()
-        .filter_map(|x| {
-            x.as_ref().ok().and_then(|x| match x.data_type {
-                RD(_) | TL(_) | AK(_) => Some(x),
-                _ => None,
-            })
-        })
+        .filter(|x| matches!(x.data_type, RD(_) | TL(_) | AK(_)))
         .collect();
 
     if syncs.len() < MIN_SYNCS_REQUIRED {
-        return Err(anyhow!("Not enough sync data"));
+        return Err(ParseError::NotEnoughSyncs {
+            found: syncs.len(),
+            required: MIN_SYNCS_REQUIRED,
+        });
     }
 
     let syncs2 = syncs.clone();
@@ -89,15 +249,52 @@ pub fn find_syncs(packets: &[Result<DataPacket>]) -> Result<Vec<TimeSync>> {
 
 /// Creates a `TimeSyncMap`
 pub fn generate_sync_map(packets: &[Result<DataPacket>]) -> Result<TimeSyncMap> {
-    let filtered = packets
-        .iter()
-        .filter_map(|result| result.as_ref().ok())
-        .map(|p| p.emotibit_timestamp);
+    sync_map_of(packets.iter().filter_map(|result| result.as_ref().ok()))
+}
 
-    let emotibit_start_time = filtered.clone().reduce(f64::min).unwrap();
-    let emotibit_end_time = filtered.reduce(f64::max).unwrap();
+/// Splits packets into sessions wherever the EmotiBit rebooted, each with its own `TimeSyncMap`
+pub fn split_sessions<I>(packets: I) -> Vec<Session>
+where
+    I: IntoIterator<Item = DataPacket>,
+{
+    let mut sessions: Vec<Vec<DataPacket>> = vec![];
+    for packet in packets {
+        match sessions.last_mut() {
+            Some(session)
+                if session
+                    .last()
+                    .is_some_and(|prev| !analysis::is_reset(prev, &packet)) =>
+            {
+                session.push(packet)
+            }
+            _ => sessions.push(vec![packet]),
+        }
+    }
+    sessions
+        .into_iter()
+        .map(|packets| Session {
+            sync_map: sync_map_of(packets.iter()),
+            packets,
+        })
+        .collect()
+}
+
+pub(crate) fn sync_map_of<'a, I>(packets: I) -> Result<TimeSyncMap>
+where
+    I: Iterator<Item = &'a DataPacket> + Clone,
+{
+    let filtered = packets.clone().map(|p| p.emotibit_timestamp);
 
-    let syncs = find_syncs(packets)?;
+    let emotibit_start_time = filtered
+        .clone()
+        .reduce(f64::min)
+        .ok_or(ParseError::NoPackets)?;
+    let emotibit_end_time = filtered.reduce(f64::max).ok_or(ParseError::NoPackets)?;
+
+    let syncs = syncs_of(packets)?;
+    if syncs.is_empty() {
+        return Err(ParseError::NoTimeSyncMap(0));
+    }
 
     let quartiles: Vec<Option<&TimeSync>> = syncs
         .chunks(num::integer::div_ceil(syncs.len(), 4))
@@ -108,7 +305,7 @@ pub fn generate_sync_map(packets: &[Result<DataPacket>]) -> Result<TimeSyncMap>
         .collect();
 
     let best_timestamps = match (
-        quartiles.get(0),
+        quartiles.first(),
         quartiles.get(1),
         quartiles.get(2),
         quartiles.get(3),
@@ -122,8 +319,7 @@ pub fn generate_sync_map(packets: &[Result<DataPacket>]) -> Result<TimeSyncMap>
         _ => None,
     };
 
-    let (p0, p1) = best_timestamps
-        .ok_or_else(|| anyhow!("Cannot generate a time sync map from these:\n{:?}", syncs))?;
+    let (p0, p1) = best_timestamps.ok_or(ParseError::NoTimeSyncMap(syncs.len()))?;
     let (tl0, te0) = get_tl_te(p0)?;
     let (tl1, te1) = get_tl_te(p1)?;
 
@@ -143,21 +339,229 @@ fn get_tl_te(sync: &TimeSync) -> Result<(f64, f64)> {
     let e0 = sync.ts_received;
     let ts = &sync.ts_sent;
 
-    let pos = ts
-        .rfind('-')
-        .ok_or_else(|| anyhow!("Invalid date string. : {:?}", sync))?;
+    let invalid = || ParseError::InvalidTimestamp(ts.to_owned());
+
+    let pos = ts.rfind('-').ok_or_else(invalid)?;
     let (head, tail) = ts.split_at(pos);
 
-    let naive_date_time = NaiveDateTime::parse_from_str(head, "%Y-%m-%d_%H-%M-%S")?;
-    let date_time: DateTime<Local> = Local.from_local_datetime(&naive_date_time).unwrap();
+    let naive_date_time =
+        NaiveDateTime::parse_from_str(head, "%Y-%m-%d_%H-%M-%S").map_err(|_| invalid())?;
+    let date_time: DateTime<Local> = Local
+        .from_local_datetime(&naive_date_time)
+        .earliest()
+        .ok_or_else(invalid)?;
     let c = date_time.timestamp();
 
     let last_n_char = tail.len() - 1;
-    let m: f64 = tail[1..].parse()?;
-    let m = m / 10_i32.pow(last_n_char.try_into()?) as f64;
+    let m: f64 = tail[1..].parse().map_err(|_| invalid())?;
+    let m = m / 10_f64.powi(last_n_char.try_into().map_err(|_| invalid())?);
 
     let mut c0 = (c as f64) + m;
-    c0 += sync.round_trip as f64 / 2_f64 / 1000_f64;
+    c0 += sync.round_trip / 2_f64 / 1000_f64;
 
     Ok((c0, e0))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Position;
+    use std::io;
+
+    const FILE: &str = "2022-09-28_10-46-19-123456.csv";
+
+    fn reader(data: &'static str) -> PacketReader<&'static [u8]> {
+        PacketReader::new(data.as_bytes()).with_file(FILE)
+    }
+
+    /// Returns `data` on the first read and an I/O error on the next
+    struct FailingReader(Option<&'static [u8]>);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.take() {
+                Some(data) => {
+                    buf[..data.len()].copy_from_slice(data);
+                    Ok(data.len())
+                }
+                None => Err(io::Error::other("connection lost")),
+            }
+        }
+    }
+
+    #[test]
+    fn streams_from_memory_with_positions() {
+        let results: Vec<_> = reader(
+            "1000,1,2,PI,1,100,156593,156471\n1001,2,1,EA,1,100,abc\n1002,3,1,EA,1,100,0.5\n",
+        )
+        .collect();
+        assert_eq!(results.len(), 3);
+
+        let file: Arc<Path> = Arc::from(Path::new(FILE));
+        let first = results[0].as_ref().unwrap();
+        assert_eq!(first.data_type, DataType::PI(vec![156593, 156471]));
+        assert_eq!(
+            first.position,
+            Some(Position {
+                file: Some(Arc::clone(&file)),
+                line: 1,
+                byte: 0
+            })
+        );
+
+        let error = results[1].as_ref().unwrap_err();
+        assert_eq!(error.kind(), "InvalidNumber");
+        assert_eq!(
+            error.position(),
+            Some(&Position {
+                file: Some(Arc::clone(&file)),
+                line: 2,
+                byte: 32
+            })
+        );
+        assert_eq!(error.record().unwrap().raw, "1001,2,1,EA,1,100,abc");
+
+        let third = results[2].as_ref().unwrap();
+        assert_eq!(
+            third.position.as_ref().map(|p| (p.line, p.byte)),
+            Some((3, 54))
+        );
+    }
+
+    #[test]
+    fn positions_without_a_file() {
+        let packet = PacketReader::new("1000,1,1,EA,1,100,0.5\n".as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.position.unwrap().file, None);
+    }
+
+    #[test]
+    fn splits_tx_packets() {
+        let results: Vec<_> = reader(
+            "1000,1,2,TX,1,100,TL,2022-09-28_10-46-20-000000,LC,1.5\n\
+             1001,2,2,TX,1,100,LC,1.5,LM,2.5\n\
+             1002,3,2,TX,1,100,LC,abc,LM,2.5\n\
+             1003,4,2,TX,1,100,XX,1,YY,2\n\
+             1004,5,1,TX,1,100,LC\n",
+        )
+        .collect();
+
+        assert_eq!(
+            results[0].as_ref().unwrap().data_type,
+            DataType::TxTlLc(("2022-09-28_10-46-20-000000".to_owned(), 1.5))
+        );
+        assert_eq!(
+            results[1].as_ref().unwrap().data_type,
+            DataType::TxLcLm((1.5, 2.5))
+        );
+        for (result, line) in results[2..4].iter().zip(3..) {
+            let error = result.as_ref().unwrap_err();
+            assert_eq!(error.kind(), "InvalidTxPayload");
+            assert_eq!(error.position().unwrap().line, line);
+            assert!(error.position().unwrap().file.is_some());
+        }
+        // Too few values to split are kept as TX
+        assert_eq!(
+            results[4].as_ref().unwrap().data_type,
+            DataType::TX(vec!["LC".to_owned()])
+        );
+    }
+
+    #[test]
+    fn csv_errors_are_located_and_do_not_stop_the_reader() {
+        let data: &[u8] = b"1000,1,1,EA,1,100,0.5\n1001,2,1,EA,1,100,\xff\n1002,3,1,EA,1,100,0.5\n";
+        let results: Vec<_> = PacketReader::new(data).with_file(FILE).collect();
+        assert_eq!(results.len(), 3);
+
+        let error = results[1].as_ref().unwrap_err();
+        assert_eq!(error.kind(), "Csv");
+        let position = error.position().unwrap();
+        assert_eq!((position.line, position.byte), (2, 22));
+        assert_eq!(position.file.as_deref(), Some(Path::new(FILE)));
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn io_errors_stop_the_reader() {
+        let mut reader = PacketReader::new(FailingReader(Some(
+            b"1000,1,1,EA,1,100,0.5\n1001,2,1,EA,1,100,0.6\n",
+        )));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        let error = reader.next().unwrap().unwrap_err();
+        assert!(matches!(&error, ParseError::Csv { source, .. } if source.is_io_error()));
+        assert!(reader.next().is_none());
+        assert!(reader.next().is_none());
+    }
+
+    const MISMATCH: &str = "1000,1,3,EA,1,100,0.1,0.2\n";
+
+    #[test]
+    fn validation_error_replaces_the_packet() {
+        let result = reader(MISMATCH)
+            .validation(Validation::Error)
+            .next()
+            .unwrap();
+        match result.unwrap_err() {
+            ParseError::DataPointsMismatch {
+                expected,
+                actual,
+                record,
+            } => {
+                assert_eq!((expected, actual), (3, 2));
+                assert_eq!(record.raw, MISMATCH.trim_end());
+                assert_eq!(
+                    record.position.unwrap().file.as_deref(),
+                    Some(Path::new(FILE))
+                );
+            }
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn validation_warn_keeps_the_packet() {
+        let packet = reader(MISMATCH).next().unwrap().unwrap();
+        assert_eq!(packet.data_type, DataType::EA(vec![0.1, 0.2]));
+        match packet.warning.as_deref() {
+            Some(Warning::DataPointsMismatch {
+                expected, actual, ..
+            }) => assert_eq!((*expected, *actual), (3, 2)),
+            None => panic!("no warning"),
+        }
+    }
+
+    #[test]
+    fn validation_ignore_keeps_the_packet_as_is() {
+        let packet = reader(MISMATCH)
+            .validation(Validation::Ignore)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.data_type, DataType::EA(vec![0.1, 0.2]));
+        assert!(packet.warning.is_none());
+    }
+
+    #[test]
+    fn validation_skips_free_text_json_and_split_tx() {
+        // None of these payloads has 9 values
+        let results: Vec<_> = reader(
+            "1000,1,9,TL,1,100,2022-09-28_10-46-20-000000\n\
+             1001,2,9,LM,1,100,marker,with,commas\n\
+             1002,3,9,RB,1,100,2022-09-28_10-46-19-123456.csv\n\
+             1003,4,9,RS,1,100,\n\
+             1004,5,9,UN,1,100,a,note\n\
+             1005,6,9,EI,1,100,{\"a\":1,\"b\":2}\n\
+             1006,7,9,TX,1,100,TL,2022-09-28_10-46-20-000000,LC,1.5\n\
+             1007,8,9,TX,1,100,LC,1.5,LM,2.5\n",
+        )
+        .validation(Validation::Error)
+        .collect();
+        assert_eq!(results.len(), 8);
+        for result in results {
+            assert!(result.unwrap().warning.is_none());
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! A raw recording parsed into packets, time syncs and metadata
+use crate::analysis;
+use crate::error::{ParseError, Result, Warning};
+use crate::info::RecordingInfo;
+use crate::metadata::MetadataTable;
+use crate::parser;
+use crate::types::{DataPacket, TimeSync, TimeSyncMap, TypeTag};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Packets, errors, time syncs and metadata of a raw recording
+///
+/// The packets are split into sessions wherever the EmotiBit rebooted. Each session gets its own `TimeSyncMap`,
+/// and its packets carry host timestamps if that map could be generated.
+#[derive(Debug)]
+pub struct Recording {
+    file_path: Option<PathBuf>,
+    packets: Vec<DataPacket>,
+    errors: Vec<ParseError>,
+    sessions: Vec<SessionSpan>,
+    info: Option<RecordingInfo>,
+    info_error: Option<ParseError>,
+    metadata: MetadataTable,
+}
+
+/// Packets of a session and what was derived from them
+#[derive(Debug)]
+struct SessionSpan {
+    range: Range<usize>,
+    syncs: Result<Vec<TimeSync>>,
+    sync_map: Result<TimeSyncMap>,
+}
+
+/// Packets recorded between two EmotiBit reboots, with their time syncs
+#[derive(Debug, Clone, Copy)]
+pub struct RecordingSession<'a> {
+    packets: &'a [DataPacket],
+    span: &'a SessionSpan,
+}
+
+impl<'a> RecordingSession<'a> {
+    /// Returns the packets of this session in the order they were recorded
+    pub fn packets(&self) -> &'a [DataPacket] {
+        self.packets
+    }
+
+    /// Returns the time syncs of this session, or why none could be found
+    pub fn time_syncs(&self) -> Result<&'a [TimeSync], &'a ParseError> {
+        self.span.syncs.as_deref()
+    }
+
+    /// Returns the `TimeSyncMap` of this session, or why it could not be generated
+    pub fn sync_map(&self) -> Result<&'a TimeSyncMap, &'a ParseError> {
+        self.span.sync_map.as_ref()
+    }
+
+    /// Returns the time between the first and the last packet in EmotiBit time
+    pub fn duration(&self) -> Option<Duration> {
+        let timestamps = self.packets.iter().map(|p| p.emotibit_timestamp);
+        let start = timestamps.clone().reduce(f64::min)?;
+        let end = timestamps.reduce(f64::max)?;
+        Some(Duration::from_secs_f64((end - start) / 1000.0))
+    }
+
+    /// Returns the time between the first and the last packet in host time, if this session has a `TimeSyncMap`
+    pub fn host_duration(&self) -> Option<Duration> {
+        let timestamps = self.packets.iter().filter_map(|p| p.host_timestamp);
+        let start = timestamps.clone().reduce(f64::min)?;
+        let end = timestamps.reduce(f64::max)?;
+        Some(Duration::from_secs_f64(end - start))
+    }
+}
+
+impl Recording {
+    /// Reads a raw csv file and its `_info.json` sidecar, if any
+    ///
+    /// A sidecar that cannot be read leaves `info` empty and is reported by `info_error`, not `errors`.
+    pub fn open<T: AsRef<Path>>(file_path: T) -> Result<Self> {
+        let (packets, info) = parser::get_packets_with_info(&file_path)?;
+        let (info, info_error) = match info {
+            Ok(info) => (info, None),
+            Err(e) => (None, Some(e)),
+        };
+        let mut recording = Self::from_packets(packets, info);
+        recording.file_path = Some(file_path.as_ref().to_owned());
+        recording.info_error = info_error;
+        Ok(recording)
+    }
+
+    /// Creates a `Recording` from parsed packets
+    pub fn from_packets(results: Vec<Result<DataPacket>>, info: Option<RecordingInfo>) -> Self {
+        let mut parsed = Vec::with_capacity(results.len());
+        let mut errors = vec![];
+        for result in results {
+            match result {
+                Ok(packet) => parsed.push(packet),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let mut packets = Vec::with_capacity(parsed.len());
+        let mut sessions = vec![];
+        for session in parser::split_sessions(parsed) {
+            let session = session.inject_host_timestamp();
+            let start = packets.len();
+            let syncs = parser::syncs_of(session.packets.iter());
+            packets.extend(session.packets);
+            sessions.push(SessionSpan {
+                range: start..packets.len(),
+                syncs,
+                sync_map: session.sync_map,
+            });
+        }
+
+        let metadata = info
+            .as_ref()
+            .map(MetadataTable::from_info)
+            .unwrap_or_default();
+
+        Recording {
+            file_path: None,
+            packets,
+            errors,
+            sessions,
+            info,
+            info_error: None,
+            metadata,
+        }
+    }
+
+    /// Returns the path of the raw data, if the recording was opened from a file
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    /// Returns all packets in the order they were recorded
+    pub fn packets(&self) -> &[DataPacket] {
+        &self.packets
+    }
+
+    /// Returns the packets of `tag`
+    pub fn packets_by_tag(&self, tag: TypeTag) -> impl Iterator<Item = &DataPacket> {
+        self.packets
+            .iter()
+            .filter(move |packet| packet.data_type.tag() == tag)
+    }
+
+    /// Returns the TypeTags present in this recording
+    pub fn tags(&self) -> BTreeSet<TypeTag> {
+        self.packets
+            .iter()
+            .map(|packet| packet.data_type.tag())
+            .collect()
+    }
+
+    /// Returns the records that failed to parse
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Returns the problems found in packets that were still kept, such as `data_points` mismatches
+    pub fn warnings(&self) -> impl Iterator<Item = &Warning> {
+        self.packets
+            .iter()
+            .filter_map(|packet| packet.warning.as_deref())
+    }
+
+    /// Returns the sessions between EmotiBit reboots in the order they were recorded
+    pub fn sessions(&self) -> impl ExactSizeIterator<Item = RecordingSession<'_>> {
+        self.sessions.iter().map(|span| RecordingSession {
+            packets: &self.packets[span.range.clone()],
+            span,
+        })
+    }
+
+    /// Returns the time syncs found in all sessions
+    pub fn time_syncs(&self) -> impl Iterator<Item = &TimeSync> {
+        self.sessions
+            .iter()
+            .filter_map(|span| span.syncs.as_ref().ok())
+            .flatten()
+    }
+
+    /// Returns the info from the `_info.json` sidecar
+    pub fn info(&self) -> Option<&RecordingInfo> {
+        self.info.as_ref()
+    }
+
+    /// Returns why the `_info.json` sidecar could not be read, if there is one that could not
+    pub fn info_error(&self) -> Option<&ParseError> {
+        self.info_error.as_ref()
+    }
+
+    /// Returns the metadata of each TypeTag, including overrides from the info file
+    pub fn metadata(&self) -> &MetadataTable {
+        &self.metadata
+    }
+
+    /// Returns the sampling rate in Hz of each TypeTag, nominal where known and inferred from the packets otherwise
+    pub fn sampling_rates(&self) -> BTreeMap<TypeTag, f64> {
+        let mut rates = analysis::infer_sampling_rates(&self.packets);
+        rates.extend(self.metadata.sampling_rates());
+        rates
+    }
+
+    /// Returns the time covered by the packets in EmotiBit time, summed over the sessions
+    pub fn duration(&self) -> Option<Duration> {
+        self.sessions()
+            .filter_map(|session| session.duration())
+            .reduce(|a, b| a + b)
+    }
+}
@@ -0,0 +1,271 @@
+//! Summary report of a recording
+use crate::analysis::{self, PacketLossReport};
+use crate::recording::Recording;
+use crate::types::{DataType, TypeTag};
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, fmt};
+
+/// Tolerance used to flag estimated sampling rates, relative to the nominal rate
+const RATE_TOLERANCE: f64 = 0.05;
+
+/// Packets, samples and sampling rates of a TypeTag
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSummary {
+    /// Number of packets
+    pub packets: usize,
+    /// Number of payload values
+    pub samples: usize,
+    /// Sampling rate in Hz estimated from the packets
+    pub estimated_rate: Option<f64>,
+    /// Nominal sampling rate in Hz
+    pub nominal_rate: Option<f64>,
+}
+
+/// Minimum, median, mean and maximum of a set of values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distribution {
+    /// Smallest value
+    pub min: f64,
+    /// Middle value
+    pub median: f64,
+    /// Average
+    pub mean: f64,
+    /// Largest value
+    pub max: f64,
+}
+
+impl Distribution {
+    fn new(mut values: Vec<f64>) -> Option<Self> {
+        values.sort_by(f64::total_cmp);
+        Some(Distribution {
+            min: *values.first()?,
+            median: values[values.len() / 2],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            max: *values.last()?,
+        })
+    }
+
+    fn to_json(self) -> Value {
+        json!({ "min": self.min, "median": self.median, "mean": self.mean, "max": self.max })
+    }
+}
+
+/// A user note with its timestamps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    /// Local timestamp on a host PC
+    pub host_timestamp: Option<f64>,
+    /// Milliseconds since start of EmotiBit
+    pub emotibit_timestamp: f64,
+    /// Text of the note
+    pub text: String,
+}
+
+/// Summary of a recording for a quick inspection without exporting it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingSummary {
+    /// Duration in EmotiBit time, in seconds, summed over the sessions
+    pub emotibit_duration: Option<f64>,
+    /// Duration in host time, in seconds, summed over the sessions that have a `TimeSyncMap`
+    pub host_duration: Option<f64>,
+    /// Packets and samples per TypeTag
+    pub tags: BTreeMap<TypeTag, TagSummary>,
+    /// Number of sessions between EmotiBit reboots
+    pub sessions: usize,
+    /// Number of time syncs
+    pub time_syncs: usize,
+    /// Round trip times of the time syncs in milliseconds
+    pub round_trip: Option<Distribution>,
+    /// Number of errors per `ParseError` kind
+    pub errors: BTreeMap<&'static str, usize>,
+    /// Number of packets that were kept despite a problem, such as a `data_points` mismatch
+    pub warnings: usize,
+    /// Packet loss based on `packet_id`
+    pub packet_loss: PacketLossReport,
+    /// Range of the battery percentage
+    pub battery: Option<(f64, f64)>,
+    /// User notes in the order they were recorded
+    pub notes: Vec<Note>,
+}
+
+impl RecordingSummary {
+    /// Builds a summary from the packets of a recording
+    pub fn new(recording: &Recording) -> Self {
+        let packets = recording.packets();
+        let estimates =
+            analysis::estimate_sampling_rates(packets, recording.metadata(), RATE_TOLERANCE);
+
+        let mut tags: BTreeMap<TypeTag, TagSummary> = BTreeMap::new();
+        for packet in packets {
+            let tag = packet.data_type.tag();
+            let summary = tags.entry(tag).or_insert_with(|| TagSummary {
+                packets: 0,
+                samples: 0,
+                estimated_rate: estimates.get(&tag).map(|e| e.rate),
+                nominal_rate: recording.metadata().get(tag).sampling_rate,
+            });
+            summary.packets += 1;
+            summary.samples += packet.data_type.payload().len();
+        }
+
+        let syncs: Vec<_> = recording.time_syncs().collect();
+
+        let mut errors = BTreeMap::new();
+        for err in recording.errors() {
+            *errors.entry(err.kind()).or_insert(0) += 1;
+        }
+
+        let battery = packets
+            .iter()
+            .filter_map(|p| match &p.data_type {
+                DataType::BATLV(v) => Some(v.iter().map(|&x| x as f64)),
+                _ => None,
+            })
+            .flatten()
+            .fold(None, |range: Option<(f64, f64)>, x| match range {
+                Some((min, max)) => Some((min.min(x), max.max(x))),
+                None => Some((x, x)),
+            });
+
+        let notes = packets
+            .iter()
+            .filter_map(|p| match &p.data_type {
+                DataType::UN(text) => Some(Note {
+                    host_timestamp: p.host_timestamp,
+                    emotibit_timestamp: p.emotibit_timestamp,
+                    text: text.join(","),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        RecordingSummary {
+            emotibit_duration: recording.duration().map(|d| d.as_secs_f64()),
+            host_duration: recording
+                .sessions()
+                .filter_map(|session| session.host_duration())
+                .reduce(|a, b| a + b)
+                .map(|d| d.as_secs_f64()),
+            tags,
+            sessions: recording.sessions().len(),
+            time_syncs: syncs.len(),
+            round_trip: Distribution::new(syncs.iter().map(|s| s.round_trip).collect()),
+            errors,
+            warnings: recording.warnings().count(),
+            packet_loss: analysis::packet_loss(packets),
+            battery,
+            notes,
+        }
+    }
+
+    /// Renders this summary as JSON
+    pub fn to_json(&self) -> Value {
+        let tags: serde_json::Map<String, Value> = self
+            .tags
+            .iter()
+            .map(|(tag, s)| {
+                (
+                    tag.to_string(),
+                    json!({
+                        "packets": s.packets,
+                        "samples": s.samples,
+                        "estimated_rate": s.estimated_rate,
+                        "nominal_rate": s.nominal_rate,
+                    }),
+                )
+            })
+            .collect();
+        let loss = &self.packet_loss;
+        json!({
+            "emotibit_duration": self.emotibit_duration,
+            "host_duration": self.host_duration,
+            "tags": tags,
+            "sessions": self.sessions,
+            "time_syncs": self.time_syncs,
+            "round_trip": self.round_trip.map(Distribution::to_json),
+            "errors": self.errors,
+            "warnings": self.warnings,
+            "packet_loss": {
+                "received": loss.received,
+                "expected": loss.expected,
+                "missing": loss.missing.len(),
+                "loss_ratio": loss.loss_ratio(),
+                "duplicates": loss.duplicates.len(),
+                "out_of_order": loss.out_of_order.len(),
+                "resets": loss.resets.len(),
+            },
+            "battery": self.battery.map(|(min, max)| json!({ "min": min, "max": max })),
+            "notes": self.notes.iter().map(|n| json!({
+                "host_timestamp": n.host_timestamp,
+                "emotibit_timestamp": n.emotibit_timestamp,
+                "text": n.text,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for RecordingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = |d: Option<f64>| d.map_or_else(|| "-".to_owned(), |d| format!("{:.3} s", d));
+        let rate = |r: Option<f64>| r.map_or_else(|| "-".to_owned(), |r| format!("{:.2}", r));
+
+        writeln!(
+            f,
+            "Duration (EmotiBit): {}",
+            seconds(self.emotibit_duration)
+        )?;
+        writeln!(f, "Duration (host):     {}", seconds(self.host_duration))?;
+        writeln!(f, "Sessions:            {}", self.sessions)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<10}{:>10}{:>10}{:>12}{:>12}",
+            "TypeTag", "Packets", "Samples", "Rate (Hz)", "Nominal"
+        )?;
+        for (tag, s) in &self.tags {
+            writeln!(
+                f,
+                "{:<10}{:>10}{:>10}{:>12}{:>12}",
+                tag.as_str(),
+                s.packets,
+                s.samples,
+                rate(s.estimated_rate),
+                rate(s.nominal_rate)
+            )?;
+        }
+        writeln!(f)?;
+        write!(f, "Time syncs: {}", self.time_syncs)?;
+        if let Some(rt) = self.round_trip {
+            write!(
+                f,
+                " (round trip min {:.1}, median {:.1}, mean {:.1}, max {:.1} ms)",
+                rt.min, rt.median, rt.mean, rt.max
+            )?;
+        }
+        writeln!(f)?;
+        let loss = &self.packet_loss;
+        writeln!(
+            f,
+            "Packet loss: {} of {} missing ({:.2}%), {} duplicates, {} out of order, {} resets",
+            loss.missing.len(),
+            loss.expected,
+            loss.loss_ratio() * 100.0,
+            loss.duplicates.len(),
+            loss.out_of_order.len(),
+            loss.resets.len()
+        )?;
+        if let Some((min, max)) = self.battery {
+            writeln!(f, "Battery: {}% - {}%", min, max)?;
+        }
+        writeln!(f, "Errors: {}", self.errors.values().sum::<usize>())?;
+        for (kind, count) in &self.errors {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+        writeln!(f, "Warnings: {}", self.warnings)?;
+        writeln!(f, "Notes: {}", self.notes.len())?;
+        for note in &self.notes {
+            writeln!(f, "  {:.3}: {}", note.emotibit_timestamp, note.text)?;
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,57 @@
 //! Types for this crate
-use anyhow::{anyhow, Result};
+use crate::error::{ParseError, Position, Record, Result, Warning};
+use crate::parser::Mode;
 use csv::StringRecord;
 use itertools::Itertools;
-use std::str::FromStr;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
 
 /// Returns CSV values
 pub trait Csv {
     fn csv(&self) -> Vec<StringRecord>;
+    /// Column names of the records returned by `csv`, if any
+    fn header(&self) -> Option<StringRecord> {
+        None
+    }
+}
+
+const PACKET_HEADER: [&str; 7] = [
+    "LocalTimestamp",
+    "EmotiBitTimestamp",
+    "PacketNumber",
+    "DataLength",
+    "TypeTag",
+    "ProtocolVersion",
+    "DataReliability",
+];
+const TIMESYNCS_HEADER: [&str; 5] = ["RD", "TS_received", "TS_sent", "AK", "RoundTrip"];
+const TIMESYNCMAP_HEADER: [&str; 8] = [
+    "TE0",
+    "TE1",
+    "TL0",
+    "TL1",
+    "TimeSyncsReceived",
+    "EmotiBitStartTime",
+    "EmotiBitEndTime",
+    "DataParserVersion",
+];
+
+/// Returns the header of packet rows with the payload column named `payload_column`
+pub(crate) fn packet_header(payload_column: &str) -> StringRecord {
+    let mut header = StringRecord::from(PACKET_HEADER.to_vec());
+    header.push_field(payload_column);
+    header
+}
+
+/// Formats a host timestamp in seconds with microsecond precision for the DataParser layout
+pub(crate) fn format_host_time(seconds: f64) -> String {
+    format!("{:.6}", seconds)
+}
+
+/// Formats an EmotiBit time in milliseconds with microsecond precision for the DataParser layout
+pub(crate) fn format_emotibit_time(milliseconds: f64) -> String {
+    format!("{:.3}", milliseconds)
 }
 
 impl Csv for StringRecord {
@@ -17,6 +62,7 @@ impl Csv for StringRecord {
 
 /// Emotibit Data Packet
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DataPacket {
     /// Local timestamp on a host PC
     pub host_timestamp: Option<f64>,
@@ -32,6 +78,12 @@ pub struct DataPacket {
     pub reliability: u8,
     /// Type of data being sent and its payload
     pub data_type: DataType,
+    /// Where the packet was found in the raw data, if it was read from a stream
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub position: Option<Position>,
+    /// Problem found in the record under `Validation::Warn`, such as a `data_points` mismatch
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub warning: Option<Box<Warning>>,
 }
 
 impl Csv for DataPacket {
@@ -56,39 +108,63 @@ impl Csv for DataPacket {
         }
         vec
     }
+
+    /// The payload column is named `Data`, so streams of mixed TypeTags share a header
+    fn header(&self) -> Option<StringRecord> {
+        Some(packet_header("Data"))
+    }
 }
 
 impl DataPacket {
-    fn parse_data_type(data_type: &DataType, payload: Vec<String>) -> Vec<String> {
+    pub(crate) fn parse_data_type(data_type: &DataType, payload: Vec<String>) -> Vec<String> {
         use DataType::*;
         match data_type {
-            TxLcLm(_) | TxTlLc(_) => vec![format!(
-                "{},{}",
-                payload.get(0).unwrap(),
-                payload.get(1).unwrap()
-            )],
+            TxLcLm(_) | TxTlLc(_) => vec![payload.join(",")],
             _ => payload,
         }
     }
     /// Performs linear interpolation based on `TimeSyncMap` and returns a new `DataPacket` with a host timestamp.
     pub fn inject_host_timestamp(self, map: &TimeSyncMap) -> Self {
-        let timestamp = map.tl0
-            + (map.tl1 - map.tl0) * (self.emotibit_timestamp - map.te0) / (map.te1 - map.te0);
         DataPacket {
-            host_timestamp: Some(timestamp),
+            host_timestamp: Some(map.host_timestamp(self.emotibit_timestamp)),
             emotibit_timestamp: self.emotibit_timestamp,
             packet_id: self.packet_id,
             data_points: self.data_points,
             version: self.version,
             reliability: self.reliability,
             data_type: self.data_type,
+            position: self.position,
+            warning: self.warning,
         }
     }
-}
 
-impl TryFrom<&StringRecord> for DataPacket {
-    type Error = anyhow::Error;
-    fn try_from(r: &StringRecord) -> Result<Self, Self::Error> {
+    /// Expands the payload into individual samples, spreading them back from the packet timestamp at `sampling_rate` Hz.
+    ///
+    /// The last sample keeps the packet timestamp. Returns an empty vector if the payload is not numeric.
+    pub fn samples(&self, sampling_rate: f64) -> Vec<Sample> {
+        let values = self.data_type.values().unwrap_or_default();
+        let period = if sampling_rate > 0.0 {
+            1000.0 / sampling_rate
+        } else {
+            0.0
+        };
+        let n = values.len();
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let offset = (n - 1 - i) as f64 * period;
+                Sample {
+                    host_timestamp: self.host_timestamp.map(|t| t - offset / 1000.0),
+                    emotibit_timestamp: self.emotibit_timestamp - offset,
+                    value,
+                }
+            })
+            .collect()
+    }
+
+    /// Creates a `DataPacket` from a record, handling unknown TypeTags according to `mode`
+    pub fn from_record(r: &StringRecord, mode: Mode) -> Result<Self> {
         if let (
             Some(timestamp),
             Some(packet_id),
@@ -100,21 +176,33 @@ impl TryFrom<&StringRecord> for DataPacket {
         {
             Ok(DataPacket {
                 host_timestamp: None,
-                emotibit_timestamp: timestamp.parse()?,
-                packet_id: packet_id.parse()?,
-                data_points: data_points.parse()?,
-                version: version.parse()?,
-                reliability: reliability.parse()?,
-                data_type: get_data_type(r, data_type)?,
+                emotibit_timestamp: parse_num(r, 0, timestamp)?,
+                packet_id: parse_num(r, 1, packet_id)?,
+                data_points: parse_num(r, 2, data_points)?,
+                version: parse_num(r, 4, version)?,
+                reliability: parse_num(r, 5, reliability)?,
+                data_type: get_data_type(r, data_type, mode)?,
+                position: r.position().map(Position::from),
+                warning: None,
             })
         } else {
-            Err(anyhow!("Missing Column, record: {:?}", r))
+            Err(ParseError::MissingColumn {
+                column: r.len(),
+                record: r.into(),
+            })
         }
     }
 }
 
+impl TryFrom<&StringRecord> for DataPacket {
+    type Error = ParseError;
+    fn try_from(r: &StringRecord) -> Result<Self, Self::Error> {
+        Self::from_record(r, Mode::Strict)
+    }
+}
+
 impl TryFrom<&str> for DataPacket {
-    type Error = anyhow::Error;
+    type Error = ParseError;
     fn try_from(str: &str) -> Result<Self, Self::Error> {
         let r = &csv::StringRecord::from(str.split(',').collect::<Vec<_>>());
         r.try_into()
@@ -122,7 +210,14 @@ impl TryFrom<&str> for DataPacket {
 }
 
 /// Emotibit data type
+///
+/// With the `serde` feature, this is serialized as `{ "type_tag": "PI", "payload": [156593, 156471] }`.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type_tag", content = "payload")
+)]
 pub enum DataType {
     /// EDA- Electrodermal Activity
     EA(Vec<f32>),
@@ -142,6 +237,8 @@ pub enum DataType {
     T1(Vec<f32>),
     /// Temperature via Medical-grade Thermopile (only on EmotiBit MD)
     TH(Vec<f32>),
+    /// Humidity 0
+    H0(Vec<f32>),
     /// Accelerometer X
     AX(Vec<f32>),
     /// Accelerometer Y
@@ -163,14 +260,17 @@ pub enum DataType {
     /// Battery Voltage
     BV(Vec<f32>),
     /// Battery Percentage Remaining (B%)
+    #[cfg_attr(feature = "serde", serde(rename = "B%"))]
     BATLV(Vec<u32>),
     AK(Vec<String>),
     /// Request Data, TypeTag in Payload
     RD(Vec<String>),
     TL(String),
     TX(Vec<String>),
+    #[cfg_attr(feature = "serde", serde(rename = "TX_TL_LC"))]
     TxTlLc((String, f32)),
-    TxLcLm(Vec<f32>),
+    #[cfg_attr(feature = "serde", serde(rename = "TX_LC_LM"))]
+    TxLcLm((f32, f32)),
     EM(Vec<String>),
     /// Heart Rate
     HR(Vec<i32>),
@@ -188,49 +288,120 @@ pub enum DataType {
     LM(String),
     /// Record begin (Include timestamp in Data)
     RB(String),
+    /// Record stop
+    RS(String),
+    /// Data Clipping, TypeTag in Payload
+    DC(Vec<PayloadTag>),
+    /// Data Overflow, TypeTag in Payload
+    DO(Vec<PayloadTag>),
+    /// Hello EmotiBit, advertising message sent by a host
+    HE(Vec<String>),
+    /// Hello Host, advertising reply sent by an EmotiBit
+    HH(Vec<String>),
+    /// Ping
+    PN(Vec<String>),
+    /// Pong
+    PO(Vec<String>),
+    /// EmotiBit Connect
+    EC(Vec<String>),
+    /// EmotiBit Disconnect
+    ED(Vec<String>),
+    /// Mode Low Power
+    ML(Vec<String>),
+    /// Mode Normal Power
+    MN(Vec<String>),
+    /// Mode Hibernate
+    MH(Vec<String>),
+    /// Mode Wireless Off
+    MO(Vec<String>),
+    /// EmotiBit Info (JSON in Data)
+    EI(serde_json::Value),
+    /// TypeTag unknown to this crate, kept as is in `Mode::Lenient`
+    Unknown {
+        tag: String,
+        payload: Vec<String>,
+    },
 }
 
 impl DataType {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DataType::Unknown { tag, .. } => tag,
+            _ => self.tag().as_str(),
+        }
+    }
+
+    /// Returns the `TypeTag` of this data type
+    pub fn tag(&self) -> TypeTag {
         use DataType::*;
         match self {
-            EA(_) => "EA",
-            EL(_) => "EL",
-            ER(_) => "ER",
-            PI(_) => "PI",
-            PR(_) => "PR",
-            PG(_) => "PG",
-            T0(_) => "T0",
-            T1(_) => "T1",
-            TH(_) => "TH",
-            AX(_) => "AX",
-            AY(_) => "AY",
-            AZ(_) => "AZ",
-            GX(_) => "GX",
-            GY(_) => "GY",
-            GZ(_) => "GZ",
-            MX(_) => "MX",
-            MY(_) => "MY",
-            MZ(_) => "MZ",
-            BV(_) => "BV",
-            BATLV(_) => "B%",
-            AK(_) => "AK",
-            RD(_) => "RD",
-            TL(_) => "TL",
-            TX(_) => "TX",
-            TxTlLc(_) => "TX_TL_LC",
-            TxLcLm(_) => "TX_LC_LM",
-            EM(_) => "EM",
-            HR(_) => "HR",
-            BI(_) => "BI",
-            SA(_) => "SA",
-            SF(_) => "SF",
-            SR(_) => "SR",
-            // Computer data TypeTags (sent over reliable channel e.g. Control)
-            UN(_) => "UN",
-            LM(_) => "LM",
-            // Control TypeTags
-            RB(_) => "RB",
+            EA(_) => TypeTag::EA,
+            EL(_) => TypeTag::EL,
+            ER(_) => TypeTag::ER,
+            PI(_) => TypeTag::PI,
+            PR(_) => TypeTag::PR,
+            PG(_) => TypeTag::PG,
+            T0(_) => TypeTag::T0,
+            T1(_) => TypeTag::T1,
+            TH(_) => TypeTag::TH,
+            H0(_) => TypeTag::H0,
+            AX(_) => TypeTag::AX,
+            AY(_) => TypeTag::AY,
+            AZ(_) => TypeTag::AZ,
+            GX(_) => TypeTag::GX,
+            GY(_) => TypeTag::GY,
+            GZ(_) => TypeTag::GZ,
+            MX(_) => TypeTag::MX,
+            MY(_) => TypeTag::MY,
+            MZ(_) => TypeTag::MZ,
+            BV(_) => TypeTag::BV,
+            BATLV(_) => TypeTag::BATLV,
+            AK(_) => TypeTag::AK,
+            RD(_) => TypeTag::RD,
+            TL(_) => TypeTag::TL,
+            TX(_) => TypeTag::TX,
+            TxTlLc(_) => TypeTag::TxTlLc,
+            TxLcLm(_) => TypeTag::TxLcLm,
+            EM(_) => TypeTag::EM,
+            HR(_) => TypeTag::HR,
+            BI(_) => TypeTag::BI,
+            SA(_) => TypeTag::SA,
+            SF(_) => TypeTag::SF,
+            SR(_) => TypeTag::SR,
+            UN(_) => TypeTag::UN,
+            LM(_) => TypeTag::LM,
+            RB(_) => TypeTag::RB,
+            RS(_) => TypeTag::RS,
+            DC(_) => TypeTag::DC,
+            DO(_) => TypeTag::DO,
+            HE(_) => TypeTag::HE,
+            HH(_) => TypeTag::HH,
+            PN(_) => TypeTag::PN,
+            PO(_) => TypeTag::PO,
+            EC(_) => TypeTag::EC,
+            ED(_) => TypeTag::ED,
+            ML(_) => TypeTag::ML,
+            MN(_) => TypeTag::MN,
+            MH(_) => TypeTag::MH,
+            MO(_) => TypeTag::MO,
+            EI(_) => TypeTag::EI,
+            Unknown { .. } => TypeTag::Unknown,
+        }
+    }
+
+    /// Returns the payload as numbers, or `None` if the payload is not a series of samples
+    pub fn values(&self) -> Option<Vec<f64>> {
+        use DataType::*;
+        fn to_f64<T: Copy + Into<f64>>(v: &[T]) -> Option<Vec<f64>> {
+            Some(v.iter().map(|&x| x.into()).collect())
+        }
+        match self {
+            EA(v) | EL(v) | ER(v) | T0(v) | T1(v) | TH(v) | H0(v) => to_f64(v),
+            AX(v) | AY(v) | AZ(v) | GX(v) | GY(v) | GZ(v) => to_f64(v),
+            BV(v) | SA(v) | SF(v) | SR(v) => to_f64(v),
+            PI(v) | PR(v) | PG(v) | BATLV(v) => to_f64(v),
+            MX(v) | MY(v) | MZ(v) | HR(v) | BI(v) => to_f64(v),
+            _ => None,
         }
     }
 
@@ -246,6 +417,7 @@ impl DataType {
             T0(v) => v.iter().map(|p| p.to_string()).collect(),
             T1(v) => v.iter().map(|p| p.to_string()).collect(),
             TH(v) => v.iter().map(|p| p.to_string()).collect(),
+            H0(v) => v.iter().map(|p| p.to_string()).collect(),
             AX(v) => v.iter().map(|p| p.to_string()).collect(),
             AY(v) => v.iter().map(|p| p.to_string()).collect(),
             AZ(v) => v.iter().map(|p| p.to_string()).collect(),
@@ -262,7 +434,7 @@ impl DataType {
             TL(s) => vec![s.to_owned()],
             TX(sv) => sv.to_vec(),
             TxTlLc((s, f)) => vec![s.to_owned(), f.to_string()],
-            TxLcLm(v) => v.iter().map(|p| p.to_string()).collect(),
+            TxLcLm((lc, lm)) => vec![lc.to_string(), lm.to_string()],
             EM(sv) => sv.to_vec(),
             HR(v) => v.iter().map(|p| p.to_string()).collect(),
             BI(v) => v.iter().map(|p| p.to_string()).collect(),
@@ -274,11 +446,297 @@ impl DataType {
             LM(s) => vec![s.to_owned()],
             // // Control TypeTags
             RB(s) => vec![s.to_owned()],
+            RS(s) => vec![s.to_owned()],
+            DC(tags) => tags.iter().map(|tag| tag.to_string()).collect(),
+            DO(tags) => tags.iter().map(|tag| tag.to_string()).collect(),
+            HE(sv) => sv.to_vec(),
+            HH(sv) => sv.to_vec(),
+            PN(sv) => sv.to_vec(),
+            PO(sv) => sv.to_vec(),
+            EC(sv) => sv.to_vec(),
+            ED(sv) => sv.to_vec(),
+            ML(sv) => sv.to_vec(),
+            MN(sv) => sv.to_vec(),
+            MH(sv) => sv.to_vec(),
+            MO(sv) => sv.to_vec(),
+            EI(json) => vec![json.to_string()],
+            Unknown { payload, .. } => payload.to_vec(),
         }
     }
 }
 
-fn get_data_type(record: &StringRecord, type_str: &str) -> Result<DataType> {
+/// EmotiBit TypeTag without a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TypeTag {
+    /// EDA- Electrodermal Activity
+    EA,
+    /// EDL- Electrodermal Level
+    EL,
+    /// EDR- Electrodermal Response (EmotiBit V4+ combines ER into EA signal)
+    ER,
+    /// PPG Infrared
+    PI,
+    /// PPG Red
+    PR,
+    /// PPG Green
+    PG,
+    /// Temperature 0
+    T0,
+    /// Temperature 1
+    T1,
+    /// Temperature via Medical-grade Thermopile (only on EmotiBit MD)
+    TH,
+    /// Humidity 0
+    H0,
+    /// Accelerometer X
+    AX,
+    /// Accelerometer Y
+    AY,
+    /// Accelerometer Z
+    AZ,
+    /// Gyroscope X
+    GX,
+    /// Gyroscope Y
+    GY,
+    /// Gyroscope Z
+    GZ,
+    /// Magnetometer X
+    MX,
+    /// Magnetometer Y
+    MY,
+    /// Magnetometer Z
+    MZ,
+    /// Battery Voltage
+    BV,
+    /// Battery Percentage Remaining (B%)
+    #[cfg_attr(feature = "serde", serde(rename = "B%"))]
+    BATLV,
+    AK,
+    /// Request Data, TypeTag in Payload
+    RD,
+    TL,
+    TX,
+    #[cfg_attr(feature = "serde", serde(rename = "TX_TL_LC"))]
+    TxTlLc,
+    #[cfg_attr(feature = "serde", serde(rename = "TX_LC_LM"))]
+    TxLcLm,
+    EM,
+    /// Heart Rate
+    HR,
+    /// Heart Inter-beat Interval
+    BI,
+    /// Skin Conductance Response (SCR) Amplitude
+    SA,
+    /// Skin Conductance Response (SCR) Frequency
+    SF,
+    /// Skin Conductance Response (SCR) Rise Time
+    SR,
+    /// User Note
+    UN,
+    /// LSL Marker/message
+    LM,
+    /// Record begin (Include timestamp in Data)
+    RB,
+    /// Record stop
+    RS,
+    /// Data Clipping, TypeTag in Payload
+    DC,
+    /// Data Overflow, TypeTag in Payload
+    DO,
+    /// Hello EmotiBit, advertising message sent by a host
+    HE,
+    /// Hello Host, advertising reply sent by an EmotiBit
+    HH,
+    /// Ping
+    PN,
+    /// Pong
+    PO,
+    /// EmotiBit Connect
+    EC,
+    /// EmotiBit Disconnect
+    ED,
+    /// Mode Low Power
+    ML,
+    /// Mode Normal Power
+    MN,
+    /// Mode Hibernate
+    MH,
+    /// Mode Wireless Off
+    MO,
+    /// EmotiBit Info (JSON in Data)
+    EI,
+    /// TypeTag unknown to this crate
+    Unknown,
+}
+
+impl TypeTag {
+    const ALL: [TypeTag; 50] = [
+        TypeTag::EA,
+        TypeTag::EL,
+        TypeTag::ER,
+        TypeTag::PI,
+        TypeTag::PR,
+        TypeTag::PG,
+        TypeTag::T0,
+        TypeTag::T1,
+        TypeTag::TH,
+        TypeTag::H0,
+        TypeTag::AX,
+        TypeTag::AY,
+        TypeTag::AZ,
+        TypeTag::GX,
+        TypeTag::GY,
+        TypeTag::GZ,
+        TypeTag::MX,
+        TypeTag::MY,
+        TypeTag::MZ,
+        TypeTag::BV,
+        TypeTag::BATLV,
+        TypeTag::AK,
+        TypeTag::RD,
+        TypeTag::TL,
+        TypeTag::TX,
+        TypeTag::TxTlLc,
+        TypeTag::TxLcLm,
+        TypeTag::EM,
+        TypeTag::HR,
+        TypeTag::BI,
+        TypeTag::SA,
+        TypeTag::SF,
+        TypeTag::SR,
+        TypeTag::UN,
+        TypeTag::LM,
+        TypeTag::RB,
+        TypeTag::RS,
+        TypeTag::DC,
+        TypeTag::DO,
+        TypeTag::HE,
+        TypeTag::HH,
+        TypeTag::PN,
+        TypeTag::PO,
+        TypeTag::EC,
+        TypeTag::ED,
+        TypeTag::ML,
+        TypeTag::MN,
+        TypeTag::MH,
+        TypeTag::MO,
+        TypeTag::EI,
+    ];
+
+    /// Returns an iterator over every known TypeTag, excluding `TypeTag::Unknown`
+    pub fn all() -> impl Iterator<Item = TypeTag> {
+        Self::ALL.into_iter()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        use TypeTag::*;
+        match self {
+            EA => "EA",
+            EL => "EL",
+            ER => "ER",
+            PI => "PI",
+            PR => "PR",
+            PG => "PG",
+            T0 => "T0",
+            T1 => "T1",
+            TH => "TH",
+            H0 => "H0",
+            AX => "AX",
+            AY => "AY",
+            AZ => "AZ",
+            GX => "GX",
+            GY => "GY",
+            GZ => "GZ",
+            MX => "MX",
+            MY => "MY",
+            MZ => "MZ",
+            BV => "BV",
+            BATLV => "B%",
+            AK => "AK",
+            RD => "RD",
+            TL => "TL",
+            TX => "TX",
+            TxTlLc => "TX_TL_LC",
+            TxLcLm => "TX_LC_LM",
+            EM => "EM",
+            HR => "HR",
+            BI => "BI",
+            SA => "SA",
+            SF => "SF",
+            SR => "SR",
+            UN => "UN",
+            LM => "LM",
+            RB => "RB",
+            RS => "RS",
+            DC => "DC",
+            DO => "DO",
+            HE => "HE",
+            HH => "HH",
+            PN => "PN",
+            PO => "PO",
+            EC => "EC",
+            ED => "ED",
+            ML => "ML",
+            MN => "MN",
+            MH => "MH",
+            MO => "MO",
+            EI => "EI",
+            Unknown => "Unknown",
+        }
+    }
+}
+
+impl fmt::Display for TypeTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A TypeTag carried in the payload of a DC or DO packet
+///
+/// With the `serde` feature, this is serialized as the TypeTag string, e.g. `"EA"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
+pub enum PayloadTag {
+    /// A TypeTag known to this crate
+    Known(TypeTag),
+    /// A TypeTag unknown to this crate, kept as is in `Mode::Lenient`
+    Other(String),
+}
+
+impl PayloadTag {
+    /// Returns the `TypeTag`, or `TypeTag::Unknown` if it is not known to this crate
+    pub fn type_tag(&self) -> TypeTag {
+        match self {
+            PayloadTag::Known(tag) => *tag,
+            PayloadTag::Other(_) => TypeTag::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for PayloadTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadTag::Known(tag) => f.write_str(tag.as_str()),
+            PayloadTag::Other(tag) => f.write_str(tag),
+        }
+    }
+}
+
+impl FromStr for TypeTag {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .find(|tag| tag.as_str() == s)
+            .ok_or_else(|| ParseError::UnknownTypeTag {
+                tag: s.to_owned(),
+                record: Record::default(),
+            })
+    }
+}
+
+fn get_data_type(record: &StringRecord, type_str: &str, mode: Mode) -> Result<DataType> {
     let skip_to_payload = 6_usize;
     match type_str {
         "RB" => Ok(DataType::RB(to_string(record, skip_to_payload))),
@@ -292,6 +750,7 @@ fn get_data_type(record: &StringRecord, type_str: &str) -> Result<DataType> {
         "T0" => Ok(DataType::T0(to_vec::<f32>(record, skip_to_payload)?)),
         "T1" => Ok(DataType::T1(to_vec::<f32>(record, skip_to_payload)?)),
         "TH" => Ok(DataType::TH(to_vec::<f32>(record, skip_to_payload)?)),
+        "H0" => Ok(DataType::H0(to_vec::<f32>(record, skip_to_payload)?)),
         "AX" => Ok(DataType::AX(to_vec::<f32>(record, skip_to_payload)?)),
         "AY" => Ok(DataType::AY(to_vec::<f32>(record, skip_to_payload)?)),
         "AZ" => Ok(DataType::AZ(to_vec::<f32>(record, skip_to_payload)?)),
@@ -314,8 +773,28 @@ fn get_data_type(record: &StringRecord, type_str: &str) -> Result<DataType> {
         "TL" => Ok(DataType::TL(to_string(record, skip_to_payload))),
         "TX" => Ok(DataType::TX(to_string_vec(record, skip_to_payload))),
         "LM" => Ok(DataType::LM(to_string(record, skip_to_payload))),
-        // TODO: add data types
-        _ => Err(anyhow!("Unknown Type: {}, {:?}", type_str, record)),
+        "RS" => Ok(DataType::RS(to_string(record, skip_to_payload))),
+        "DC" => Ok(DataType::DC(to_tag_vec(record, skip_to_payload, mode)?)),
+        "DO" => Ok(DataType::DO(to_tag_vec(record, skip_to_payload, mode)?)),
+        "HE" => Ok(DataType::HE(to_string_vec(record, skip_to_payload))),
+        "HH" => Ok(DataType::HH(to_string_vec(record, skip_to_payload))),
+        "PN" => Ok(DataType::PN(to_string_vec(record, skip_to_payload))),
+        "PO" => Ok(DataType::PO(to_string_vec(record, skip_to_payload))),
+        "EC" => Ok(DataType::EC(to_string_vec(record, skip_to_payload))),
+        "ED" => Ok(DataType::ED(to_string_vec(record, skip_to_payload))),
+        "ML" => Ok(DataType::ML(to_string_vec(record, skip_to_payload))),
+        "MN" => Ok(DataType::MN(to_string_vec(record, skip_to_payload))),
+        "MH" => Ok(DataType::MH(to_string_vec(record, skip_to_payload))),
+        "MO" => Ok(DataType::MO(to_string_vec(record, skip_to_payload))),
+        "EI" => Ok(DataType::EI(to_json(record, skip_to_payload)?)),
+        _ if mode == Mode::Lenient => Ok(DataType::Unknown {
+            tag: type_str.to_owned(),
+            payload: to_string_vec(record, skip_to_payload),
+        }),
+        _ => Err(ParseError::UnknownTypeTag {
+            tag: type_str.to_owned(),
+            record: record.into(),
+        }),
     }
 }
 
@@ -327,20 +806,24 @@ fn to_string(record: &StringRecord, index: usize) -> String {
 fn to_vec<T>(record: &StringRecord, index_from: usize) -> Result<Vec<T>>
 where
     T: num::Num + FromStr,
-    <T as std::str::FromStr>::Err: std::fmt::Debug,
 {
-    let mut errors = vec![];
-    let vec = record
+    record
         .iter()
+        .enumerate()
         .skip(index_from)
-        .map(|x| x.to_string().trim().parse::<T>())
-        .filter_map(|r| r.map_err(|e| errors.push(e)).ok())
-        .collect::<Vec<T>>();
-    if errors.is_empty() {
-        Ok(vec)
-    } else {
-        Err(anyhow!("Parse to Num Error: {:?}", record))
-    }
+        .map(|(column, value)| parse_num(record, column, value))
+        .collect()
+}
+
+fn parse_num<T: FromStr>(record: &StringRecord, column: usize, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| ParseError::InvalidNumber {
+            column,
+            value: value.to_owned(),
+            record: Record::from(record),
+        })
 }
 
 fn to_string_vec(record: &StringRecord, index_from: usize) -> Vec<String> {
@@ -351,8 +834,43 @@ fn to_string_vec(record: &StringRecord, index_from: usize) -> Vec<String> {
         .collect()
 }
 
+fn to_tag_vec(record: &StringRecord, index_from: usize, mode: Mode) -> Result<Vec<PayloadTag>> {
+    record
+        .iter()
+        .skip(index_from)
+        .map(|tag| match tag.parse() {
+            Ok(known) => Ok(PayloadTag::Known(known)),
+            Err(_) if mode == Mode::Lenient => Ok(PayloadTag::Other(tag.to_owned())),
+            Err(_) => Err(ParseError::UnknownTypeTag {
+                tag: tag.to_owned(),
+                record: record.into(),
+            }),
+        })
+        .collect()
+}
+
+// The JSON object spans several columns as it contains commas
+fn to_json(record: &StringRecord, index: usize) -> Result<serde_json::Value> {
+    serde_json::from_str(&to_string(record, index)).map_err(|source| ParseError::InvalidJson {
+        source,
+        record: record.into(),
+    })
+}
+
+/// A single sample of a packet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Local timestamp on a host PC
+    pub host_timestamp: Option<f64>,
+    /// Milliseconds since start of EmotiBit
+    pub emotibit_timestamp: f64,
+    /// Value of the sample
+    pub value: f64,
+}
+
 /// Time Syncs
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TimeSync {
     /// Emotibit local time when RD was sent
     pub rd: f64,
@@ -369,17 +887,22 @@ pub struct TimeSync {
 impl Csv for TimeSync {
     fn csv(&self) -> Vec<StringRecord> {
         vec![StringRecord::from(vec![
-            self.rd.to_string(),
-            self.ts_received.to_string(),
+            format_emotibit_time(self.rd),
+            format_emotibit_time(self.ts_received),
             self.ts_sent.to_owned(),
-            self.ak.to_string(),
-            self.round_trip.to_string(),
+            format_emotibit_time(self.ak),
+            format_emotibit_time(self.round_trip),
         ])]
     }
+
+    fn header(&self) -> Option<StringRecord> {
+        Some(StringRecord::from(TIMESYNCS_HEADER.to_vec()))
+    }
 }
 
 /// Time Sync Map
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TimeSyncMap {
     pub te0: f64,
     pub te1: f64,
@@ -391,17 +914,163 @@ pub struct TimeSyncMap {
     pub parse_version: String,
 }
 
+impl TimeSyncMap {
+    /// Converts milliseconds since start of EmotiBit into a host timestamp by linear interpolation
+    pub fn host_timestamp(&self, emotibit_timestamp: f64) -> f64 {
+        self.tl0 + (self.tl1 - self.tl0) * (emotibit_timestamp - self.te0) / (self.te1 - self.te0)
+    }
+}
+
 impl Csv for TimeSyncMap {
     fn csv(&self) -> Vec<StringRecord> {
         vec![StringRecord::from(vec![
-            self.te0.to_string(),
-            self.te1.to_string(),
-            self.tl0.to_string(),
-            self.tl1.to_string(),
+            format_emotibit_time(self.te0),
+            format_emotibit_time(self.te1),
+            format_host_time(self.tl0),
+            format_host_time(self.tl1),
             self.syncs_received.to_string(),
-            self.emotibit_start_time.to_string(),
-            self.emotibit_end_time.to_string(),
+            format_emotibit_time(self.emotibit_start_time),
+            format_emotibit_time(self.emotibit_end_time),
             self.parse_version.to_owned(),
         ])]
     }
+
+    fn header(&self) -> Option<StringRecord> {
+        Some(StringRecord::from(TIMESYNCMAP_HEADER.to_vec()))
+    }
+}
+
+/// Packets recorded between two EmotiBit reboots
+#[derive(Debug)]
+pub struct Session {
+    /// Packets of this session in the order they were recorded
+    pub packets: Vec<DataPacket>,
+    /// `TimeSyncMap` generated from the time syncs of this session
+    pub sync_map: Result<TimeSyncMap>,
+}
+
+impl Session {
+    /// Injects host timestamps into all packets of this session. Packets are returned as is if there is no `TimeSyncMap`.
+    pub fn inject_host_timestamp(self) -> Self {
+        let packets = match &self.sync_map {
+            Ok(map) => self
+                .packets
+                .into_iter()
+                .map(|p| p.inject_host_timestamp(map))
+                .collect(),
+            Err(_) => self.packets,
+        };
+        Session {
+            packets,
+            sync_map: self.sync_map,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(line: &str) -> StringRecord {
+        StringRecord::from(line.split(',').collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn unknown_payload_tags_round_trip_in_lenient_mode() {
+        let record = record("1000,1,2,DC,1,100,EA,QQ");
+        let packet = DataPacket::from_record(&record, Mode::Lenient).unwrap();
+        assert_eq!(
+            packet.data_type,
+            DataType::DC(vec![
+                PayloadTag::Known(TypeTag::EA),
+                PayloadTag::Other("QQ".to_owned())
+            ])
+        );
+        assert_eq!(packet.data_type.payload(), ["EA", "QQ"]);
+        let rows: Vec<String> = packet.csv().iter().map(|r| r[7].to_owned()).collect();
+        assert_eq!(rows, ["EA", "QQ"]);
+
+        let error = DataPacket::from_record(&record, Mode::Strict).unwrap_err();
+        assert!(matches!(error, ParseError::UnknownTypeTag { tag, .. } if tag == "QQ"));
+    }
+
+    /// Serializes `data_type` as part of a packet, checks the JSON and deserializes it back
+    #[cfg(feature = "serde")]
+    fn round_trip(data_type: DataType, expected: serde_json::Value) {
+        let packet = DataPacket {
+            host_timestamp: None,
+            emotibit_timestamp: 1126349.0,
+            packet_id: 49106,
+            data_points: 2,
+            version: 1,
+            reliability: 100,
+            data_type,
+            position: None,
+            warning: None,
+        };
+        let json = serde_json::to_value(&packet).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "host_timestamp": null,
+                "emotibit_timestamp": 1126349.0,
+                "packet_id": 49106,
+                "data_points": 2,
+                "version": 1,
+                "reliability": 100,
+                "data_type": expected,
+            })
+        );
+        let back: DataPacket = serde_json::from_value(json).unwrap();
+        assert_eq!(back.data_type, packet.data_type);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_schema() {
+        use serde_json::json;
+        round_trip(
+            DataType::PI(vec![156593, 156471]),
+            json!({ "type_tag": "PI", "payload": [156593, 156471] }),
+        );
+        round_trip(
+            DataType::BATLV(vec![87]),
+            json!({ "type_tag": "B%", "payload": [87] }),
+        );
+        round_trip(
+            DataType::TxTlLc(("2022-09-28_10-46-20-000000".to_owned(), 1.5)),
+            json!({ "type_tag": "TX_TL_LC", "payload": ["2022-09-28_10-46-20-000000", 1.5] }),
+        );
+        round_trip(
+            DataType::TxLcLm((1.5, 2.5)),
+            json!({ "type_tag": "TX_LC_LM", "payload": [1.5, 2.5] }),
+        );
+        round_trip(
+            DataType::EI(json!({ "device": "EmotiBit", "version": 4 })),
+            json!({ "type_tag": "EI", "payload": { "device": "EmotiBit", "version": 4 } }),
+        );
+        round_trip(
+            DataType::Unknown {
+                tag: "XX".to_owned(),
+                payload: vec!["1".to_owned(), "a".to_owned()],
+            },
+            json!({ "type_tag": "Unknown", "payload": { "tag": "XX", "payload": ["1", "a"] } }),
+        );
+        round_trip(
+            DataType::DC(vec![
+                PayloadTag::Known(TypeTag::EA),
+                PayloadTag::Other("XX".to_owned()),
+            ]),
+            json!({ "type_tag": "DC", "payload": ["EA", "XX"] }),
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_tx_payloads_of_the_wrong_length() {
+        for payload in [serde_json::json!([]), serde_json::json!([1.5])] {
+            let json = serde_json::json!({ "type_tag": "TX_LC_LM", "payload": payload });
+            assert!(serde_json::from_value::<DataType>(json).is_err());
+        }
+    }
 }
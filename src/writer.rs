@@ -1,26 +1,79 @@
 //! Writer types and functions
-use crate::types::Csv;
+use crate::types::{Csv, DataPacket, TypeTag};
 use anyhow::Result;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// When `Writer` flushes its buffer to the underlying writer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// After every call to `Writer::write`
+    EveryWrite,
+    /// After every `n` records
+    EveryRecords(usize),
+    /// Only on `Writer::flush` and when the `Writer` is dropped
+    ///
+    /// Flushing on drop relies on `csv::Writer`'s `Drop`, which ignores I/O errors.
+    /// Call `Writer::flush` or `Writer::into_inner` when done to see them.
+    #[default]
+    OnDrop,
+}
 
 /// Use `WriterBuilder` to build this struct.
-pub struct Writer {
-    writer: csv::Writer<std::fs::File>,
+pub struct Writer<W: Write = File> {
+    writer: csv::Writer<W>,
+    flush_policy: FlushPolicy,
+    unflushed: usize,
+    has_headers: bool,
+    header_written: bool,
 }
 
-impl Writer {
-    /// Writes a `DataPacket` to a file
-    pub fn write<T: Csv>(&mut self, datapacket: &T) -> Result<()> {
-        for item in datapacket.csv() {
-            self.writer.write_record(&item)?;
+impl<W: Write> Writer<W> {
+    /// Writes the records of an item, such as a `DataPacket` or a `TimeSync`
+    ///
+    /// The header of the first item written, if any, is written before it.
+    pub fn write<T: Csv>(&mut self, item: &T) -> Result<()> {
+        if self.has_headers && !self.header_written {
+            if let Some(header) = item.header() {
+                self.writer.write_record(&header)?;
+                self.unflushed += 1;
+            }
+            self.header_written = true;
+        }
+        for record in item.csv() {
+            self.writer.write_record(&record)?;
+            self.unflushed += 1;
+        }
+        match self.flush_policy {
+            FlushPolicy::EveryWrite => self.flush()?,
+            FlushPolicy::EveryRecords(n) if self.unflushed >= n => self.flush()?,
+            _ => (),
         }
+        Ok(())
+    }
+
+    /// Flushes buffered records to the underlying writer
+    pub fn flush(&mut self) -> Result<()> {
         self.writer.flush()?;
+        self.unflushed = 0;
         Ok(())
     }
+
+    /// Flushes and returns the underlying writer
+    pub fn into_inner(self) -> Result<W> {
+        self.writer.into_inner().map_err(|e| e.into_error().into())
+    }
 }
 
 /// Builder struct for `Writer`
 pub struct WriterBuilder {
     builder: csv::WriterBuilder,
+    flush_policy: FlushPolicy,
+    has_headers: bool,
 }
 
 impl Default for WriterBuilder {
@@ -33,12 +86,191 @@ impl WriterBuilder {
     pub fn new() -> Self {
         WriterBuilder {
             builder: csv::WriterBuilder::new(),
+            flush_policy: FlushPolicy::default(),
+            has_headers: true,
         }
     }
+    /// Whether to write a header before the first item. Defaults to `true`.
+    pub fn has_headers(mut self, yes: bool) -> Self {
+        self.has_headers = yes;
+        self
+    }
+    /// Sets when `Writer` flushes. Defaults to `FlushPolicy::OnDrop`.
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
     /// Creates `Writer` with a flie path
-    pub fn from_path(mut self, path: &str) -> Result<Writer> {
+    pub fn from_path<P: AsRef<Path>>(mut self, path: P) -> Result<Writer> {
         Ok(Writer {
             writer: self.builder.flexible(true).from_path(path)?,
+            flush_policy: self.flush_policy,
+            unflushed: 0,
+            has_headers: self.has_headers,
+            header_written: false,
         })
     }
+    /// Creates `Writer` with any `io::Write`, such as stdout, a socket or an in-memory buffer
+    pub fn from_writer<W: Write>(mut self, writer: W) -> Writer<W> {
+        Writer {
+            writer: self.builder.flexible(true).from_writer(writer),
+            flush_policy: self.flush_policy,
+            unflushed: 0,
+            has_headers: self.has_headers,
+            header_written: false,
+        }
+    }
+}
+
+/// Writes packets to one `<stem>_<TAG>.csv` file per TypeTag in a directory
+///
+/// Files are created as new TypeTags appear, each starting with a header.
+/// Packets of unknown TypeTags all go to `<stem>_Unknown.csv`, so the raw data cannot pick file names.
+pub struct SplitWriter {
+    dir: PathBuf,
+    stem: String,
+    flush_policy: FlushPolicy,
+    writers: BTreeMap<TypeTag, Writer>,
+}
+
+impl SplitWriter {
+    /// Creates `SplitWriter` with an existing directory and a filename stem
+    pub fn new<P: AsRef<Path>>(dir: P, stem: &str) -> Self {
+        SplitWriter {
+            dir: dir.as_ref().to_owned(),
+            stem: stem.to_owned(),
+            flush_policy: FlushPolicy::default(),
+            writers: BTreeMap::new(),
+        }
+    }
+
+    /// Sets when each file is flushed. Defaults to `FlushPolicy::OnDrop`.
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    /// Writes a `DataPacket` to the file of its TypeTag
+    pub fn write(&mut self, packet: &DataPacket) -> Result<()> {
+        self.write_tagged(packet.data_type.tag(), packet)
+    }
+
+    /// Writes any `Csv` item to the file of `tag`
+    pub fn write_tagged<T: Csv>(&mut self, tag: TypeTag, item: &T) -> Result<()> {
+        let writer = match self.writers.get_mut(&tag) {
+            Some(writer) => writer,
+            None => {
+                let writer = WriterBuilder::new()
+                    .flush_policy(self.flush_policy)
+                    .from_path(self.path(tag))?;
+                self.writers.entry(tag).or_insert(writer)
+            }
+        };
+        writer.write(item)
+    }
+
+    /// Returns the paths of the files created so far
+    pub fn paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.writers.keys().map(|&tag| self.path(tag))
+    }
+
+    fn path(&self, tag: TypeTag) -> PathBuf {
+        self.dir.join(format!("{}_{}.csv", self.stem, tag))
+    }
+
+    /// Flushes all files
+    pub fn flush(&mut self) -> Result<()> {
+        self.writers.values_mut().try_for_each(Writer::flush)
+    }
+
+    /// Flushes and closes all files
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(line: &str) -> DataPacket {
+        DataPacket::try_from(line).unwrap()
+    }
+
+    /// Bytes that reached the underlying writer so far
+    fn flushed(writer: &Writer<Vec<u8>>) -> usize {
+        writer.writer.get_ref().len()
+    }
+
+    #[test]
+    fn writes_to_memory() {
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(vec![]);
+        writer.write(&packet("1000,1,2,EA,1,100,0.5,0.6")).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "NaN,1000,1,2,EA,1,100,0.5\nNaN,1000,1,2,EA,1,100,0.6\n"
+        );
+    }
+
+    #[test]
+    fn flush_policies() {
+        let line = "1000,1,1,EA,1,100,0.5";
+
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .flush_policy(FlushPolicy::EveryRecords(2))
+            .from_writer(vec![]);
+        writer.write(&packet(line)).unwrap();
+        assert_eq!(flushed(&writer), 0);
+        writer.write(&packet(line)).unwrap();
+        let two_records = flushed(&writer);
+        assert_eq!(two_records, 2 * (line.len() + 5));
+        writer.write(&packet(line)).unwrap();
+        assert_eq!(flushed(&writer), two_records);
+        writer.write(&packet(line)).unwrap();
+        assert_eq!(flushed(&writer), 2 * two_records);
+
+        let mut writer = WriterBuilder::new()
+            .flush_policy(FlushPolicy::EveryWrite)
+            .from_writer(vec![]);
+        writer.write(&packet(line)).unwrap();
+        assert!(flushed(&writer) > 0);
+
+        let mut writer = WriterBuilder::new().from_writer(vec![]);
+        writer.write(&packet(line)).unwrap();
+        assert_eq!(flushed(&writer), 0);
+        writer.flush().unwrap();
+        assert!(flushed(&writer) > 0);
+    }
+
+    #[test]
+    fn header_is_written_on_the_first_write_only() {
+        let mut writer = WriterBuilder::new().from_writer(vec![]);
+        writer.write(&packet("1000,1,1,EA,1,100,0.5")).unwrap();
+        writer.write(&packet("1001,2,1,PI,1,100,156593")).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "LocalTimestamp,EmotiBitTimestamp,PacketNumber,DataLength,TypeTag,ProtocolVersion,DataReliability,Data\n\
+             NaN,1000,1,1,EA,1,100,0.5\n\
+             NaN,1001,2,1,PI,1,100,156593\n"
+        );
+    }
+
+    #[test]
+    fn no_header_after_an_item_without_one() {
+        let mut writer = WriterBuilder::new().from_writer(vec![]);
+        writer
+            .write(&csv::StringRecord::from(vec!["a", "b"]))
+            .unwrap();
+        writer.write(&packet("1000,1,1,EA,1,100,0.5")).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(output, "a,b\nNaN,1000,1,1,EA,1,100,0.5\n");
+
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(vec![]);
+        writer.write(&packet("1000,1,1,EA,1,100,0.5")).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(output, "NaN,1000,1,1,EA,1,100,0.5\n");
+    }
 }
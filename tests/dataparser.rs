@@ -0,0 +1,42 @@
+//! Compares `export_dataparser_layout` with a snapshot of its output for the fixture in `tests/data/dataparser`
+//!
+//! The expected files were written by this crate, not by the EmotiBit DataParser, so this guards against regressions only.
+use emotibit_data::{export, recording::Recording};
+use std::{fs, path::Path};
+
+const FIXTURE: &str = "tests/data/dataparser";
+
+#[test]
+fn export_matches_snapshot() {
+    // Host timestamps depend on the local time zone of the time syncs
+    std::env::set_var("TZ", "UTC");
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURE);
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dataparser");
+    let _ = fs::remove_dir_all(&out_dir);
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let recording = Recording::open(fixture.join("2022-09-28_10-46-19-123456.csv")).unwrap();
+    export::export_dataparser_layout(&recording, &out_dir).unwrap();
+
+    let mut expected: Vec<_> = fs::read_dir(fixture.join("expected"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    let mut actual: Vec<_> = fs::read_dir(&out_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    expected.sort();
+    actual.sort();
+    assert_eq!(actual, expected);
+
+    for name in expected {
+        assert_eq!(
+            fs::read_to_string(out_dir.join(&name)).unwrap(),
+            fs::read_to_string(fixture.join("expected").join(&name)).unwrap(),
+            "{} differs",
+            name.to_string_lossy()
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Reads EDF+ files written by `EdfWriter` back and checks the header, record sizes and annotations
+use emotibit_data::{edf::EdfWriterBuilder, recording::Recording, types::DataPacket};
+use std::path::Path;
+
+const RAW: &str = "tests/data/dataparser/2022-09-28_10-46-19-123456.csv";
+
+/// Onset in seconds and annotations of a time-stamped annotation list
+type Tal = (f64, Vec<String>);
+
+fn text(bytes: &[u8], offset: usize, len: usize) -> String {
+    String::from_utf8_lossy(&bytes[offset..offset + len])
+        .trim_end()
+        .to_owned()
+}
+
+fn number(bytes: &[u8], offset: usize, len: usize) -> usize {
+    text(bytes, offset, len).parse().unwrap()
+}
+
+/// Splits the annotation signal of a data record into TALs of an onset and its annotations
+fn tals(bytes: &[u8]) -> Vec<Tal> {
+    bytes
+        .split(|&b| b == 0)
+        .filter(|tal| !tal.is_empty())
+        .map(|tal| {
+            let tal = String::from_utf8_lossy(tal);
+            let mut parts = tal.split('\x14');
+            let onset = parts.next().unwrap().parse().unwrap();
+            let annotations = parts.filter(|a| !a.is_empty()).map(str::to_owned).collect();
+            (onset, annotations)
+        })
+        .collect()
+}
+
+/// Number of signals, samples per data record of each signal, and the TALs of each data record
+fn read_back(edf: &[u8]) -> (usize, Vec<usize>, Vec<Vec<Tal>>) {
+    let ns = number(edf, 252, 4);
+    assert_eq!(number(edf, 184, 8), 256 * (ns + 1));
+    // Samples per data record follow label, transducer, unit, physical and digital ranges and prefiltering
+    let offset = 256 + ns * (16 + 80 + 8 + 8 + 8 + 8 + 8 + 80);
+    let samples: Vec<usize> = (0..ns).map(|i| number(edf, offset + 8 * i, 8)).collect();
+
+    let record_size = samples.iter().sum::<usize>() * 2;
+    let records = number(edf, 236, 8);
+    assert_eq!(edf.len(), 256 * (ns + 1) + records * record_size);
+
+    // The annotation signal comes last in each data record
+    let annotation_offset = record_size - samples[ns - 1] * 2;
+    let tals = edf[256 * (ns + 1)..]
+        .chunks(record_size)
+        .map(|record| tals(&record[annotation_offset..]))
+        .collect();
+    (ns, samples, tals)
+}
+
+/// Physical minimum and maximum of each signal
+fn physical_ranges(edf: &[u8], ns: usize) -> (Vec<f64>, Vec<f64>) {
+    // Physical ranges follow label, transducer and unit
+    let offset = 256 + ns * (16 + 80 + 8);
+    let field = |i: usize| text(edf, offset + 8 * i, 8).parse::<f64>().unwrap();
+    (
+        (0..ns).map(field).collect(),
+        (ns..2 * ns).map(field).collect(),
+    )
+}
+
+#[test]
+fn header_records_and_annotations_read_back() {
+    // The start time is taken from host timestamps in the local time zone
+    std::env::set_var("TZ", "UTC");
+
+    let recording = Recording::open(Path::new(env!("CARGO_MANIFEST_DIR")).join(RAW)).unwrap();
+    let edf = EdfWriterBuilder::new()
+        .from_writer(vec![])
+        .write(&recording)
+        .unwrap();
+
+    assert_eq!(text(&edf, 0, 8), "0");
+    assert_eq!(text(&edf, 168, 8), "28.09.22");
+    assert_eq!(text(&edf, 176, 8), "10.46.20");
+    assert!(text(&edf, 192, 44).starts_with("EDF+C"));
+    assert_eq!(text(&edf, 244, 8), "1");
+    let (ns, samples, records) = read_back(&edf);
+    assert_eq!(records.len(), 13);
+
+    let labels: Vec<String> = (0..ns).map(|i| text(&edf, 256 + 16 * i, 16)).collect();
+    assert_eq!(labels, ["EA", "PI", "EDF Annotations"]);
+    assert_eq!(text(&edf, 256 + 16 * ns, 80), "EmotiBit EDA");
+    assert_eq!(samples[..2], [15, 25]);
+    // Physical ranges come from the TypeTag metadata, not from the values
+    let (physical_min, physical_max) = physical_ranges(&edf, ns);
+    assert_eq!(physical_min[..2], [0.0, 0.0]);
+    assert_eq!(physical_max[..2], [100.0, 262143.0]);
+
+    // Every data record starts with a time-keeping TAL one record duration after the previous one
+    let start = records[0][0].0;
+    assert!((0.0..1.0).contains(&start));
+    for (i, tals) in records.iter().enumerate() {
+        assert!((tals[0].0 - (start + i as f64)).abs() < 1e-6);
+        assert!(tals[0].1.is_empty());
+    }
+
+    // The note 12.5 s after the first packet is in the last data record
+    let notes: Vec<(usize, f64, &[String])> = records
+        .iter()
+        .enumerate()
+        .flat_map(|(i, tals)| tals[1..].iter().map(move |(t, a)| (i, *t, a.as_slice())))
+        .collect();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].0, 12);
+    assert!((notes[0].1 - (start + 12.5)).abs() < 1e-6);
+    assert_eq!(notes[0].2, ["start of task"]);
+}
+
+#[test]
+fn sessions_are_written_one_at_a_time() {
+    let lines = [
+        "1000,1,3,EA,1,100,0.1,0.2,0.3",
+        "2000,2,3,EA,1,100,0.4,0.5,0.6",
+        "100,1,3,EA,1,100,0.7,0.8,0.9",
+        "1100,2,3,EA,1,100,1.0,1.1,1.2",
+    ];
+    let packets = lines
+        .iter()
+        .map(|&line| DataPacket::try_from(line))
+        .collect();
+    let recording = Recording::from_packets(packets, None);
+    assert_eq!(recording.sessions().len(), 2);
+
+    assert!(EdfWriterBuilder::new()
+        .from_writer(vec![])
+        .write(&recording)
+        .is_err());
+    for session in recording.sessions() {
+        let edf = EdfWriterBuilder::new()
+            .from_writer(vec![])
+            .write_session(&recording, session)
+            .unwrap();
+        // Each session lasts 1 s from its own first packet
+        let (_, _, records) = read_back(&edf);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], [(0.0, vec![])]);
+        assert_eq!(records[1], [(1.0, vec![])]);
+    }
+}
+
+#[test]
+fn data_range_for_tags_without_a_physical_range() {
+    let lines = [
+        "1000,1,1,HR,1,100,61",
+        "2000,2,1,HR,1,100,79",
+        "3000,3,1,HR,1,100,70",
+        "4000,4,1,HR,1,100,65",
+    ];
+    let packets = lines
+        .iter()
+        .map(|&line| DataPacket::try_from(line))
+        .collect();
+    let recording = Recording::from_packets(packets, None);
+    let edf = EdfWriterBuilder::new()
+        .from_writer(vec![])
+        .write(&recording)
+        .unwrap();
+
+    let (ns, _, _) = read_back(&edf);
+    assert_eq!(text(&edf, 256, 16), "HR");
+    let (physical_min, physical_max) = physical_ranges(&edf, ns);
+    assert_eq!((physical_min[0], physical_max[0]), (61.0, 79.0));
+}
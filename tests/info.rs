@@ -0,0 +1,104 @@
+//! Reads an `_info.json` sidecar in the layout written by the EmotiBit firmware
+use emotibit_data::{
+    error::ParseError,
+    info::{self, RecordingInfo},
+    metadata::MetadataTable,
+    types::TypeTag,
+};
+use std::{fs, path::Path};
+
+const INFO: &str = "tests/data/info/2022-09-28_10-46-19-123456_info.json";
+
+fn fixture() -> RecordingInfo {
+    RecordingInfo::from_path(Path::new(env!("CARGO_MANIFEST_DIR")).join(INFO)).unwrap()
+}
+
+#[test]
+fn recording_info_from_str() {
+    let text = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join(INFO)).unwrap();
+    let info: RecordingInfo = text.parse().unwrap();
+    assert_eq!(info, fixture());
+
+    assert_eq!(info.device_id.as_deref(), Some("MD-V4-0000321"));
+    assert_eq!(info.hardware_version.as_deref(), Some("V04a"));
+    assert_eq!(
+        info.feather_version.as_deref(),
+        Some("Adafruit Feather HUZZAH32")
+    );
+    assert_eq!(info.firmware_version.as_deref(), Some("1.3.36"));
+    assert_eq!(
+        info.firmware_variant.as_deref(),
+        Some("EmotiBit_stock_firmware")
+    );
+    assert_eq!(
+        info.created_at.as_deref(),
+        Some("2022-09-28_10-46-19-123456")
+    );
+
+    let names: Vec<&str> = info.sensors.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "Accelerometer",
+            "Gyroscope",
+            "Magnetometer",
+            "ElectrodermalActivity",
+            "Temperature",
+            "PPG"
+        ]
+    );
+
+    let ppg = info.sensor(TypeTag::PR).unwrap();
+    assert_eq!(ppg.type_tags, ["PI", "PR", "PG"]);
+    assert_eq!(ppg.sampling_rate, Some(25.0));
+    assert_eq!(ppg.channel_count, Some(3));
+    assert_eq!(ppg.channel_format.as_deref(), Some("float"));
+    assert_eq!(ppg.setup["LED_power_level"], "47");
+
+    let eda = info.sensor(TypeTag::EA).unwrap();
+    assert_eq!(eda.kind.as_deref(), Some("ElectrodermalActivity"));
+    assert_eq!(eda.units.as_deref(), Some("microsiemens"));
+    assert_eq!(eda.setup["eda_transform_slope"], "0.775");
+
+    // Sampling rates are written as strings by some firmware versions
+    assert_eq!(info.sensor(TypeTag::T1).unwrap().sampling_rate, Some(7.5));
+    assert!(info.sensor(TypeTag::TH).is_none());
+}
+
+#[test]
+fn metadata_table_from_info() {
+    let table = MetadataTable::from_info(&fixture());
+
+    assert_eq!(table.get(TypeTag::AX).unit.as_deref(), Some("G"));
+    assert_eq!(table.get(TypeTag::EA).unit.as_deref(), Some("microsiemens"));
+    assert_eq!(table.get(TypeTag::T1).sampling_rate, Some(7.5));
+    // `N/A` units keep the built-in unit
+    assert_eq!(table.get(TypeTag::PI).unit, TypeTag::PI.metadata().unit);
+    // TypeTags missing from the info keep the built-in metadata
+    assert_eq!(table.get(TypeTag::TH), TypeTag::TH.metadata());
+    assert_eq!(table.get(TypeTag::EL), TypeTag::EL.metadata());
+
+    let rates = table.sampling_rates();
+    assert_eq!(rates[&TypeTag::MZ], 25.0);
+    assert_eq!(rates[&TypeTag::EA], 15.0);
+}
+
+#[test]
+fn unreadable_sidecar_keeps_the_source_error_and_path() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("info");
+    fs::create_dir_all(&dir).unwrap();
+    let raw = dir.join("broken.csv");
+    fs::write(&raw, "").unwrap();
+    fs::write(dir.join("broken_info.json"), "[{\"info\":").unwrap();
+
+    match info::read_sidecar(&raw) {
+        Err(ParseError::InfoFile { path, source }) => {
+            assert_eq!(path, dir.join("broken_info.json"));
+            assert!(matches!(*source, ParseError::Json(_)));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    fs::remove_file(dir.join("broken_info.json")).unwrap();
+    assert!(info::read_sidecar(&raw).unwrap().is_none());
+}
@@ -0,0 +1,82 @@
+//! Checks `RecordingSummary` and its renderings on a recording with EmotiBit reboots
+use emotibit_data::{
+    recording::Recording,
+    summary::RecordingSummary,
+    types::{DataPacket, TypeTag},
+};
+
+/// A session of 12.3 s with four time syncs, starting at `host_minute` on the host
+fn session(host_minute: u32) -> Vec<String> {
+    let mut lines = vec!["1000,1,3,EA,1,100,0.1,0.2,0.3".to_owned()];
+    for k in 0..4 {
+        let t = 1200 + 4000 * k;
+        let id = 2 + 3 * k;
+        lines.push(format!("{},{},1,RD,1,100,TL", t, id));
+        lines.push(format!(
+            "{},{},1,TL,1,100,2022-09-28_10-{:02}-{:02}-000000",
+            t + 10,
+            id + 1,
+            host_minute,
+            4 * k
+        ));
+        lines.push(format!("{},{},2,AK,1,100,{},RD", t + 20, id + 2, id));
+    }
+    lines.push("13300,14,1,UN,1,100,end of session".to_owned());
+    lines
+}
+
+fn recording() -> Recording {
+    // The EmotiBit is off for about a minute between the sessions, and the last session has no time syncs
+    let mut lines = session(46);
+    lines.extend(session(48));
+    lines.extend(["1000,1,1,EA,1,100,0.1", "3000,2,1,EA,1,100,0.2"].map(str::to_owned));
+    let packets = lines
+        .iter()
+        .map(|line| DataPacket::try_from(line.as_str()))
+        .collect();
+    Recording::from_packets(packets, None)
+}
+
+#[test]
+fn durations_are_summed_over_sessions() {
+    std::env::set_var("TZ", "UTC");
+    let summary = RecordingSummary::new(&recording());
+
+    assert_eq!(summary.sessions, 3);
+    assert!((summary.emotibit_duration.unwrap() - 26.6).abs() < 1e-6);
+    // The downtime between the sessions and the session without a `TimeSyncMap` are left out
+    assert!((summary.host_duration.unwrap() - 24.6).abs() < 1e-6);
+    assert_eq!(summary.time_syncs, 8);
+    assert_eq!(summary.round_trip.unwrap().median, 10.0);
+    assert_eq!(summary.notes.len(), 2);
+    assert!(summary.notes.iter().all(|n| n.host_timestamp.is_some()));
+    assert_eq!(summary.tags[&TypeTag::EA].samples, 8);
+    assert!(summary.errors.is_empty());
+}
+
+#[test]
+fn renderings() {
+    std::env::set_var("TZ", "UTC");
+    let summary = RecordingSummary::new(&recording());
+
+    let json = summary.to_json();
+    assert!((json["emotibit_duration"].as_f64().unwrap() - 26.6).abs() < 1e-6);
+    assert!((json["host_duration"].as_f64().unwrap() - 24.6).abs() < 1e-6);
+    assert_eq!(json["sessions"], 3);
+    assert_eq!(json["time_syncs"], 8);
+    assert_eq!(json["tags"]["EA"]["packets"], 4);
+    assert_eq!(json["tags"]["EA"]["nominal_rate"], 15.0);
+    assert_eq!(json["notes"][1]["text"], "end of session");
+    assert_eq!(json["battery"], serde_json::Value::Null);
+
+    let text = summary.to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "Duration (EmotiBit): 26.600 s");
+    assert_eq!(lines[1], "Duration (host):     24.600 s");
+    assert_eq!(lines[2], "Sessions:            3");
+    assert!(text.contains("\nEA                 4         8"));
+    assert!(text
+        .contains("\nTime syncs: 8 (round trip min 10.0, median 10.0, mean 10.0, max 10.0 ms)\n"));
+    assert!(text.contains("\nErrors: 0\n"));
+    assert!(text.ends_with("  13300.000: end of session\n"));
+}
@@ -0,0 +1,86 @@
+//! Writes a packet stream with `SplitWriter` and checks the files it creates
+use emotibit_data::{
+    parser::{Mode, PacketReader},
+    types::DataPacket,
+    writer::SplitWriter,
+};
+use std::{fs, path::Path};
+
+const HEADER: &str =
+    "LocalTimestamp,EmotiBitTimestamp,PacketNumber,DataLength,TypeTag,ProtocolVersion,DataReliability";
+
+fn packets(data: &'static str) -> Vec<DataPacket> {
+    PacketReader::new(data.as_bytes())
+        .mode(Mode::Lenient)
+        .map(Result::unwrap)
+        .collect()
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn one_file_per_tag() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("split_writer");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let packets = packets(
+        "1000,1,2,EA,1,100,0.1,0.2\n\
+         1010,2,1,PI,1,100,156593\n\
+         1020,3,1,XX,1,100,a\n\
+         1030,4,1,EA,1,100,0.3\n\
+         1040,5,1,..,1,100,b\n",
+    );
+    let mut writer = SplitWriter::new(&dir, "rec");
+
+    // Files are created as their TypeTags appear
+    writer.write(&packets[0]).unwrap();
+    assert_eq!(names(&dir), ["rec_EA.csv"]);
+    writer.write(&packets[1]).unwrap();
+    assert_eq!(names(&dir), ["rec_EA.csv", "rec_PI.csv"]);
+    for packet in &packets[2..] {
+        writer.write(packet).unwrap();
+    }
+    assert_eq!(
+        writer.paths().collect::<Vec<_>>(),
+        [
+            dir.join("rec_EA.csv"),
+            dir.join("rec_PI.csv"),
+            dir.join("rec_Unknown.csv")
+        ]
+    );
+    writer.finish().unwrap();
+    assert_eq!(names(&dir), ["rec_EA.csv", "rec_PI.csv", "rec_Unknown.csv"]);
+
+    // Each file starts with a single header
+    assert_eq!(
+        fs::read_to_string(dir.join("rec_EA.csv")).unwrap(),
+        format!(
+            "{HEADER},Data\n\
+             NaN,1000,1,2,EA,1,100,0.1\n\
+             NaN,1000,1,2,EA,1,100,0.2\n\
+             NaN,1030,4,1,EA,1,100,0.3\n"
+        )
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("rec_PI.csv")).unwrap(),
+        format!("{HEADER},Data\nNaN,1010,2,1,PI,1,100,156593\n")
+    );
+
+    // Unknown TypeTags share one file and keep their own tags
+    assert_eq!(
+        fs::read_to_string(dir.join("rec_Unknown.csv")).unwrap(),
+        format!(
+            "{HEADER},Data\n\
+             NaN,1020,3,1,XX,1,100,a\n\
+             NaN,1040,5,1,..,1,100,b\n"
+        )
+    );
+}